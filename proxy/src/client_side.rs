@@ -1,204 +1,123 @@
-use bytes::{BufMut, Bytes, BytesMut};
+use crate::message_id;
 use log::{error, info};
-use silkrust::construct_processor_table;
+use silkrust::net::framework::ModuleIdentification;
 use silkrust::net::message::Message;
-use silkrust::net::message::MessageDirection::Req;
-use silkrust::net::message::MessageKind::NetEngine;
-use silkrust::net::net_engine::{
-    ErrorDetectionSeed, ExchangeResponse, ExchangeSetup, HandshakeOptions,
+use silkrust::net::message::MessageDirection::{Ack, NoDir, Req};
+use silkrust::net::message::MessageKind::{Framework, NetEngine};
+use silkrust::net::net_engine::HandshakeInitiator;
+use silkrust::net::{
+    AsyncMessageTable, AsyncProcess, AsyncProcessor, DisconnectReason, NetClient, NetClientHandle,
+    NetEvent, Process, ProcessFuture,
 };
-use silkrust::net::{MessageTable, NetClient, Process, Processor};
-use silkrust::security::blowfish_compat::{BlowfishCompat, NewBlockCipher};
-use silkrust::security::{Challenge, Exchange, Initiator, NotSet, SecurityBuilder};
-use std::sync::mpsc::{Receiver, Sender, TryRecvError};
-use std::sync::{Arc, RwLock};
-use silkrust::net::io::BytesExtension;
+use silkrust::security::Exchange;
+use std::sync::Arc;
+use tokio::sync::Notify;
 
 struct ModuleIdentificationProcessor {
-    sender: Sender<Message>,
-}
-impl ModuleIdentificationProcessor {
-    fn new(sender: Sender<Message>) -> Self {
-        Self { sender }
-    }
+    server: NetClientHandle,
 }
 
-struct HandshakeReqProcessor {
-    context_builder: Exchange<NotSet>,
-}
-impl HandshakeReqProcessor {
-    pub fn new(context_builder: Exchange<NotSet>) -> Self {
-        Self { context_builder }
-    }
-}
-
-#[derive(Default)]
-struct HandshakeAckProcessor {
-    can_receive_forwarded_messages: Arc<RwLock<bool>>,
-}
+impl AsyncProcess for ModuleIdentificationProcessor {
+    fn process<'a>(&'a mut self, net_client: &'a mut NetClient, m: Message) -> ProcessFuture<'a> {
+        Box::pin(async move {
+            match ModuleIdentification::try_from(m.clone()) {
+                Ok(identification) => net_client.identify(&identification.name),
+                Err(e) => error!("could not read module identification ({})", e),
+            }
 
-impl HandshakeAckProcessor {
-    pub fn new(can_receive_forwarded_messages: Arc<RwLock<bool>>) -> Self {
-        Self {
-            can_receive_forwarded_messages,
-        }
+            if self.server.send(m).await.is_err() {
+                error!("could not forward module identification, server is gone");
+            }
+        })
     }
 }
 
 struct ServerForwardProcessor {
-    sender: Sender<Message>,
+    server: NetClientHandle,
 }
 
-impl ServerForwardProcessor {
-    fn new(sender: Sender<Message>) -> Self {
-        Self { sender }
+impl AsyncProcess for ServerForwardProcessor {
+    fn process<'a>(&'a mut self, _net_client: &'a mut NetClient, m: Message) -> ProcessFuture<'a> {
+        Box::pin(async move {
+            if let Err(e) = self.server.send(m).await {
+                error!("could not forward {}, server is gone", e.0);
+            }
+        })
     }
 }
 
-impl Process for HandshakeAckProcessor {
-    fn process(&mut self, net_client: &mut NetClient, m: Message) {
-        let mut v = self.can_receive_forwarded_messages.write().unwrap();
-        *v = true;
-        info!("[Handshake 🤝] completed with key exchange ✅!");
-    }
+/// Drives the handshake with the client and signals once it completed
+#[derive(Clone)]
+struct HandshakeGate {
+    initiator: HandshakeInitiator,
+    completed: Arc<Notify>,
 }
-impl Process for HandshakeReqProcessor {
-    fn process(&mut self, net_client: &mut NetClient, m: Message) {
-        let mem: Bytes = m.reader();
-        let response = ExchangeResponse::from(mem);
-
-        let exchange = self.context_builder.remote(response.public);
-        if let Err(_) = <Initiator as Challenge>::verify(&exchange, response.signature) {
-            net_client.close();
-            error!("remote signature does not match calculate signature");
-            panic!();
-        }
-
-        // challenge is ok!
-        let challenge = <Initiator as Challenge>::create(&exchange);
-
-        let options = HandshakeOptions::new().with_challenge(true);
-        let mut data = BytesMut::new();
-        data.put_u8(options.into());
-        data.put_slice(challenge.as_slice());
 
-        let m = Message::new(Req, NetEngine, 0, data.freeze());
-        net_client.send(m);
-
-        // finalize security
-        let final_key = <Initiator as Challenge>::finalize(&exchange);
-        let blowfish =
-            BlowfishCompat::new_from_slice(&final_key).expect("could not initialize blowfish");
-
-        info!("[Handshake 🤝] 🙋🏽‍♂️ Sending Challenge");
-        net_client.security_mut().blowfish = Some(blowfish);
-    }
-}
-impl Process for ServerForwardProcessor {
-    fn process(&mut self, _net_client: &mut NetClient, m: Message) {
-        self.sender.send(m).expect("channel is not clogged");
-    }
-}
-
-impl Process for ModuleIdentificationProcessor {
+impl Process for HandshakeGate {
     fn process(&mut self, net_client: &mut NetClient, m: Message) {
-        self.sender.send(m.clone()).expect("channel is not clogged");
-        let mut reader = m.reader();
-        let name = reader.get_string().unwrap();
-        net_client.identify(name.as_str());
+        Process::process(&mut self.initiator, net_client, m);
+
+        while let Some(event) = net_client.take_event() {
+            if event == NetEvent::HandshakeCompleted {
+                self.completed.notify_one();
+            }
+        }
     }
 }
 
 pub struct ClientSide {
     client_connection: NetClient,
-    receiver: Receiver<Message>,
+
+    /// handle of the connection to the server NetEngine
+    server: NetClientHandle,
 }
 
 impl ClientSide {
-    pub fn new(client_connection: NetClient, receiver: Receiver<Message>) -> Self {
+    pub fn new(client_connection: NetClient, server: NetClientHandle) -> Self {
         Self {
             client_connection,
-            receiver,
+            server,
         }
     }
 
-    pub fn run(&mut self, sender: Sender<Message>) {
-        let exchange = Exchange::default()
-            .set_initial(rand::random())
-            .set_generator(rand::random::<u32>() & 0x7FFFFFFF)
-            .set_prime(rand::random::<u32>() & 0x7FFFFFFF)
-            .set_private(rand::random::<u32>() & 0x7FFFFFFF);
+    /// Runs until the client disconnects, `handshake_completed` is notified once server
+    /// messages can be forwarded to the client.
+    pub async fn run(mut self, handshake_completed: Arc<Notify>) -> DisconnectReason {
+        // the proxy forwards pings, it never sends its own
+        self.client_connection.set_keep_alive(None);
+        // massive fragments are forwarded as they arrive
+        self.client_connection.set_massive_reassembly(false);
 
-        // initiate handshake
-        self.init_handshake(&exchange);
+        let exchange = Exchange::random();
 
-        let can_receive_forwarded_messages = Arc::new(RwLock::new(false));
+        // initiate handshake
+        let initiator = HandshakeInitiator::new(exchange);
+        initiator.start(&mut self.client_connection);
 
-        let handshake_ack_processor =
-            HandshakeAckProcessor::new(can_receive_forwarded_messages.clone());
-        let mut forwarder: Processor = Box::new(ServerForwardProcessor::new(sender.clone()));
-        let mut message_table: MessageTable = construct_processor_table! {
-            NetEngine, 0, Ack = HandshakeAckProcessor = handshake_ack_processor,
-            NetEngine, 0, Req = HandshakeReqProcessor = HandshakeReqProcessor::new(exchange),
-            Framework, 1, NoDir = ModuleIdentificationProcessor = ModuleIdentificationProcessor::new(sender)
+        let gate = HandshakeGate {
+            initiator,
+            completed: handshake_completed,
         };
 
-        // loop
-        loop {
-            self.client_connection
-                .process_messages(&mut message_table, &mut forwarder, 100);
-            if let Ok(v) = can_receive_forwarded_messages.try_read() {
-                if *v {
-                    // send received messages
-                    match self.receiver.try_recv() {
-                        /// client message can be sent to server
-                        Ok(m) => {
-                            self.client_connection.send(m);
-                        }
-
-                        /// exit loop if sender has disconnected
-                        Err(TryRecvError::Disconnected) => {
-                            return;
-                        }
-                        _ => {}
-                    }
-                }
-            }
-        }
-    }
-
-    fn init_handshake(&mut self, exchange: &Exchange<NotSet>) {
-        let (sequence_seed, checksum_seed): (u32, u32) = (rand::random(), rand::random());
-
-        let security = SecurityBuilder::default()
-            .encoding_requirements((true, false))
-            .error_detection((sequence_seed, checksum_seed))
-            .build();
-
-        let options = HandshakeOptions::new()
-            .with_disabled(false)
-            .with_challenge(false)
-            .with_encryption(false)
-            .with_exchange(true)
-            .with_error_detection(true);
-
-        self.client_connection.set_security(security);
-
-        // building the message
-        let error_detection = ErrorDetectionSeed::new(sequence_seed, checksum_seed);
-        let setup = ExchangeSetup::new(
-            exchange.get_initial(),
-            exchange.get_generator(),
-            exchange.get_prime(),
-            exchange.get_local(),
+        let mut message_table = AsyncMessageTable::new();
+        message_table.insert(message_id(NetEngine, 0, Ack), Box::new(gate.clone()));
+        message_table.insert(message_id(NetEngine, 0, Req), Box::new(gate));
+        message_table.insert(
+            message_id(Framework, 1, NoDir),
+            Box::new(ModuleIdentificationProcessor {
+                server: self.server.clone(),
+            }),
         );
 
-        let mut data = BytesMut::new();
-        data.put_u8(options.into());
-        data.put::<Bytes>(error_detection.into());
-        data.put::<Bytes>(setup.into());
+        let mut forwarder: AsyncProcessor = Box::new(ServerForwardProcessor {
+            server: self.server,
+        });
 
-        let m = Message::new(Req, NetEngine, 0, data.freeze());
-        self.client_connection.send(m);
+        let reason = self
+            .client_connection
+            .run(&mut message_table, &mut forwarder)
+            .await;
+        info!("client disconnected ({})", reason);
+        reason
     }
 }
@@ -1,18 +1,25 @@
-use bytes::BufMut;
-use log::info;
-use silkrust::net::message::Message;
-use silkrust::net::{NetClient, Process};
-use silkrust::security::blowfish_compat::NewBlockCipher;
-use silkrust::security::Challenge;
-use std::sync::mpsc;
-use std::thread;
-use tokio::net::TcpListener;
 use crate::client_side::ClientSide;
 use crate::server_side::ServerSide;
+use silkrust::net::message::{MessageDirection, MessageId, MessageKind};
+use silkrust::net::NetClient;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tokio::sync::mpsc::channel;
+use tokio::sync::Notify;
 
 mod client_side;
 mod server_side;
 
+/// Number of server messages held back until the handshake with the client completed
+const FORWARD_CAPACITY: usize = 1024;
+
+fn message_id(kind: MessageKind, op: usize, dir: MessageDirection) -> MessageId {
+    MessageId::new()
+        .with_operation(op)
+        .with_kind(kind)
+        .with_direction(dir)
+}
+
 #[tokio::main]
 async fn main() {
     // todo move to config
@@ -24,30 +31,37 @@ async fn main() {
     let server_connection = NetClient::connect(remote_endpoint.as_str())
         .await
         .expect("could not connect to remote");
+    let server_handle = server_connection.handle();
 
-    let (server_send, server_receive) = mpsc::channel::<Message>();
-    let (client_send, client_receive) = mpsc::channel::<Message>();
+    let (server_send, mut server_receive) = channel(FORWARD_CAPACITY);
+    let server_side = tokio::spawn(ServerSide::new(server_connection, server_send).run());
 
-    let mut server_side = ServerSide::new(server_connection, client_receive);
-    let server_handle = thread::Builder::new().stack_size(1024 * 1024 * 4).spawn(move || server_side.run(server_send)).unwrap();
-
-
-    let listener = TcpListener::bind(listener_endpoint.as_str()).await.expect("could not start listening");
+    let listener = TcpListener::bind(listener_endpoint.as_str())
+        .await
+        .expect("could not start listening");
     let (stream, _addr) = listener.accept().await.unwrap();
     let client: NetClient = stream.into();
+    let client_handle = client.handle();
 
-    let mut client_side = ClientSide::new(client, server_receive);
-    let client_handle = thread::Builder::new().stack_size(1024 * 1024 * 8).spawn(move || client_side.run(client_send)).unwrap();
+    let handshake_completed = Arc::new(Notify::new());
+    let client_side = tokio::spawn(
+        ClientSide::new(client, server_handle.clone()).run(handshake_completed.clone()),
+    );
 
-    if let Ok(()) = server_handle.join() {
-        info!("server handle ok");
-    } else {
-        info!("server handle not ok");
-    }
+    // server messages reach the client once the client completed its handshake
+    let forward_handle = client_handle.clone();
+    tokio::spawn(async move {
+        handshake_completed.notified().await;
+        while let Some(m) = server_receive.recv().await {
+            if forward_handle.send(m).await.is_err() {
+                break;
+            }
+        }
+    });
 
-    if let Ok(()) = client_handle.join() {
-        info!("client handle ok");
-    } else {
-        info!("client handle not ok");
+    // once either side disconnects, the other one is closed as well
+    tokio::select! {
+        _ = server_side => client_handle.close().await,
+        _ = client_side => server_handle.close().await,
     }
-}
\ No newline at end of file
+}
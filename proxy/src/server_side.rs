@@ -1,128 +1,31 @@
-use bytes::{Buf, Bytes};
-use log::info;
-use silkrust::construct_processor_table;
+use crate::message_id;
+use log::{error, info};
+use silkrust::net::framework::ModuleIdentification;
 use silkrust::net::message::Message;
-use silkrust::net::message::MessageDirection::{Ack, Req};
-use silkrust::net::message::MessageKind::NetEngine;
-use silkrust::net::net_engine::{
-    ErrorDetectionSeed, ExchangeResponse, ExchangeSetup, HandshakeOptions,
+use silkrust::net::message::MessageDirection::{NoDir, Req};
+use silkrust::net::message::MessageKind::{Framework, NetEngine};
+use silkrust::net::net_engine::HandshakeResponder;
+use silkrust::net::{
+    AsyncMessageTable, AsyncProcess, AsyncProcessor, DisconnectReason, NetClient, ProcessFuture,
 };
-use silkrust::net::{MessageTable, NetClient, Process, Processor};
-use silkrust::security::blowfish_compat::{BlowfishCompat, NewBlockCipher};
-use silkrust::security::{Challenge, Exchange, Key, Responder, SecurityBuilder, Set, Signature};
-use std::sync::mpsc::{Receiver, Sender, TryRecvError};
-use silkrust::net::io::BytesExtension;
+use tokio::sync::mpsc::Sender;
 
 struct ModuleIdentificationProcessor {
     sender: Sender<Message>,
 }
 
-impl ModuleIdentificationProcessor {
-    fn new(sender: Sender<Message>) -> Self {
-        Self {
-            sender
-        }
-    }
-}
-
-impl Process for ModuleIdentificationProcessor {
-    fn process(&mut self, net_client: &mut NetClient, m: Message) {
-        self.sender.send(m.clone()).expect("channel is not clogged");
-        let mut reader = m.reader();
-        let name = reader.get_string().unwrap();
-        net_client.identify(name.as_str());
-    }
-}
-
-#[derive(Default)]
-struct ResponderHandshakeReqProcessor {
-    exchange: Exchange<Set>,
-}
-
-impl Process for ResponderHandshakeReqProcessor {
-    fn process(&mut self, net_client: &mut NetClient, m: Message) {
-        let mut reader = m.reader();
-
-        let options = HandshakeOptions::from(reader.get_u8());
-        if options.challenge() {
-            self.handle_challenge(reader, net_client);
-        } else {
-            self.handle_setup(options, reader, net_client);
-        }
-    }
-}
-
-impl ResponderHandshakeReqProcessor {
-    fn handle_challenge(&mut self, mut reader: Bytes, net_client: &mut NetClient) {
-        let mut signature = Signature::default();
-        reader.copy_to_slice(&mut signature);
-
-        if let Err(_) = <Responder as Challenge>::verify(&self.exchange, signature) {
-            panic!("signature mismatch");
-        }
-
-        // update security with final blowfish instance
-        let key = <Responder as Challenge>::finalize(&self.exchange);
-        let blowfish = BlowfishCompat::new_from_slice(key.as_slice())
-            .expect("after exchange key should be valid");
-        net_client.security_mut().blowfish = Some(blowfish);
-
-        info!("[Handshake 🤝] completed with key exchange ✅!");
-
-        net_client.send(Message::new(Ack, NetEngine, 0, Bytes::new()));
-    }
-
-    fn handle_setup(
-        &mut self,
-        options: HandshakeOptions,
-        mut reader: Bytes,
-        net_client: &mut NetClient,
-    ) {
-        let mut security_builder = SecurityBuilder::default();
-
-        info!("[Handshake 🤝] 🙋🏽‍♂️ Setting Up");
-
-        if options.encryption() {
-            let mut key = Key::default();
-            reader.copy_to_slice(&mut key);
-            security_builder = security_builder.blowfish(key);
-
-            info!("[Handshake 🤝] (in-)secure blowfish initialized");
-        }
-
-        if options.error_detection() {
-            let error_detection = ErrorDetectionSeed::from(reader.copy_to_bytes(8));
-            security_builder = security_builder
-                .encoding_requirements((false, true))
-                .error_detection((error_detection.sequence, error_detection.checksum));
-
-            info!("[Handshake 🤝] error detection initialized");
-        }
-
-        net_client.set_security(security_builder.build());
-
-        let message = if options.exchange() {
-            let setup = ExchangeSetup::from(reader.copy_to_bytes(20));
-
-            self.exchange = Exchange::default()
-                .set_initial(setup.initial_key)
-                .set_generator(setup.generator)
-                .set_prime(setup.prime)
-                .set_private(rand::random())
-                .remote(setup.public);
-
-            let signature = <Responder as Challenge>::create(&self.exchange);
-            let response = ExchangeResponse::new(self.exchange.get_local(), signature);
-
-            let mem: Bytes = response.into();
-            info!("[Handshake 🤝] responded to key exchange setup!");
-            Message::new(Req, NetEngine, 0, mem)
-        } else {
-            info!("[Handshake 🤝] completed without key exchange ✅!");
-            Message::new(Ack, NetEngine, 0, Bytes::new())
-        };
+impl AsyncProcess for ModuleIdentificationProcessor {
+    fn process<'a>(&'a mut self, net_client: &'a mut NetClient, m: Message) -> ProcessFuture<'a> {
+        Box::pin(async move {
+            match ModuleIdentification::try_from(m.clone()) {
+                Ok(identification) => net_client.identify(&identification.name),
+                Err(e) => error!("could not read module identification ({})", e),
+            }
 
-        net_client.send(message);
+            if self.sender.send(m).await.is_err() {
+                error!("could not forward module identification, client is gone");
+            }
+        })
     }
 }
 
@@ -130,65 +33,60 @@ struct ClientForwardProcessor {
     sender: Sender<Message>,
 }
 
-impl ClientForwardProcessor {
-    fn new(sender: Sender<Message>) -> Self {
-        Self {
-            sender
-        }
-    }
-}
-
-impl Process for ClientForwardProcessor {
-    fn process(&mut self, _net_client: &mut NetClient, m: Message) {
-        self.sender.send(m).expect("channel is not clogged");
+impl AsyncProcess for ClientForwardProcessor {
+    fn process<'a>(&'a mut self, _net_client: &'a mut NetClient, m: Message) -> ProcessFuture<'a> {
+        Box::pin(async move {
+            if let Err(e) = self.sender.send(m).await {
+                error!("could not forward {}, client is gone", e.0);
+            }
+        })
     }
 }
 
 pub struct ServerSide {
-    /// sends messages to the client-side queue
-    // sender: Sender<Message>,
-
-    /// receives message from the client-side queue
-    receiver: Receiver<Message>,
+    /// sends messages to the client side, which forwards them once its handshake completed
+    sender: Sender<Message>,
 
     /// connection to the server NetEngine (e.g. GatewayServer, AgentServer, ...)
     server_connection: NetClient,
 }
 
 impl ServerSide {
-    pub fn new(server_connection: NetClient, receiver: Receiver<Message>) -> Self {
+    pub fn new(server_connection: NetClient, sender: Sender<Message>) -> Self {
         Self {
             server_connection,
-            receiver,
+            sender,
         }
     }
 
-    pub fn run(&mut self, sender: Sender<Message>) {
-        let mut message_table: MessageTable = construct_processor_table! {
-            Framework, 1, NoDir = ModuleIdentificationProcessor = ModuleIdentificationProcessor::new(sender.clone()),
-            NetEngine, 0, Req = ResponderHandshakeReqProcessor = ResponderHandshakeReqProcessor::default()
-        };
-
-        let mut forwarder: Processor = Box::new(ClientForwardProcessor::new(sender.clone()));
-
-        loop {
-            // process server messages
-            self.server_connection
-                .process_messages(&mut message_table, &mut forwarder, 100);
-
-            // send received messages
-            match self.receiver.try_recv() {
-                /// client message can be sent to server
-                Ok(m) => {
-                    self.server_connection.send(m);
-                },
-
-                /// exit loop if sender has disconnected
-                Err(TryRecvError::Disconnected) => {
-                    return;
-                }
-                _ => {}
-            }
-        }
+    /// Runs until the server disconnects.
+    pub async fn run(mut self) -> DisconnectReason {
+        // the proxy forwards pings, it never sends its own
+        self.server_connection.set_keep_alive(None);
+        // massive fragments are forwarded as they arrive
+        self.server_connection.set_massive_reassembly(false);
+
+        let mut message_table = AsyncMessageTable::new();
+        message_table.insert(
+            message_id(Framework, 1, NoDir),
+            Box::new(ModuleIdentificationProcessor {
+                sender: self.sender.clone(),
+            }),
+        );
+        message_table.insert(
+            message_id(NetEngine, 0, Req),
+            Box::new(HandshakeResponder::default()),
+        );
+
+        let mut forwarder: AsyncProcessor = Box::new(ClientForwardProcessor {
+            sender: self.sender,
+        });
+
+        let reason = self
+            .server_connection
+            .run(&mut message_table, &mut forwarder)
+            .await;
+        info!("server disconnected ({})", reason);
+        reason
     }
 }
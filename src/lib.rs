@@ -1,2 +1,5 @@
 pub mod net;
 pub mod security;
+
+#[cfg(test)]
+mod test_support;
@@ -7,10 +7,30 @@ mod message_buffer;
 pub use self::net_connection::NetConnection;
 mod net_connection;
 
-pub use self::net_client::{MessageTable, NetClient, Process, Processor};
+pub use self::net_client::{
+    AsyncMessageTable, AsyncProcess, AsyncProcessor, MessageTable, NetClient, Process,
+    ProcessFuture, Processor, DEFAULT_KEEP_ALIVE_INTERVAL, DEFAULT_MAX_MASSIVE_SIZE,
+    MAX_QUEUED_EVENTS,
+};
 mod net_client;
 
+pub use self::net_client_handle::NetClientHandle;
+mod net_client_handle;
+
+pub use self::net_event::NetEvent;
+mod net_event;
+
+pub use self::disconnect_reason::DisconnectReason;
+mod disconnect_reason;
+
+pub use self::outbound_error::OutboundError;
+mod outbound_error;
+
 mod massive;
 
+pub use self::codec::{CodecError, SilkroadCodec};
+mod codec;
+
+pub mod framework;
 pub mod io;
 pub mod net_engine;
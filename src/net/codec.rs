@@ -0,0 +1,197 @@
+use crate::net::massive;
+use crate::net::message::{Message, MessageParseError, MAX_MESSAGE_SIZE};
+use crate::net::message_buffer::split_message;
+use crate::security::{Desync, Security, SecurityError};
+use bytes::{BufMut, Bytes, BytesMut};
+use std::fmt::{Display, Formatter};
+use tokio_util::codec::{Decoder, Encoder};
+
+/// Frames a byte stream into [Message]s and back.
+///
+/// Use it with `tokio_util::codec::Framed` to plug the Silkroad framing into any transport. If a
+/// [Security] is set, inbound messages are decrypted and verified, and outbound messages are
+/// encoded with it. Without one, messages pass through untouched, which is what a transparent
+/// proxy wants. Outbound messages that are too large are split into massive fragments, those too
+/// large even for that fail with [MessageParseError::Oversized].
+pub struct SilkroadCodec {
+    security: Option<Security>,
+    max_message_size: usize,
+}
+
+impl Default for SilkroadCodec {
+    fn default() -> Self {
+        Self {
+            security: None,
+            max_message_size: MAX_MESSAGE_SIZE,
+        }
+    }
+}
+
+impl SilkroadCodec {
+    pub fn new(security: Security) -> Self {
+        Self {
+            security: Some(security),
+            ..Default::default()
+        }
+    }
+
+    /// Sets the largest inbound message accepted, larger ones fail with
+    /// [MessageParseError::Oversized]. Defaults to [MAX_MESSAGE_SIZE].
+    pub fn set_max_message_size(&mut self, max_message_size: usize) {
+        self.max_message_size = max_message_size;
+    }
+
+    pub fn set_security(&mut self, security: Security) {
+        self.security = Some(security);
+    }
+
+    pub fn security_mut(&mut self) -> Option<&mut Security> {
+        self.security.as_mut()
+    }
+}
+
+impl Decoder for SilkroadCodec {
+    type Item = Message;
+    type Error = CodecError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        let Some(message) = split_message(src, self.max_message_size)? else {
+            return Ok(None);
+        };
+
+        match &mut self.security {
+            Some(security) => {
+                let message = security.decrypt(message)?;
+                security.verify(&message)?;
+                Ok(Some(message))
+            }
+            None => Ok(Some(message)),
+        }
+    }
+}
+
+impl Encoder<Message> for SilkroadCodec {
+    type Error = CodecError;
+
+    fn encode(&mut self, item: Message, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        for message in massive::split(item)? {
+            let message = match &mut self.security {
+                Some(security) => security.encode(message),
+                None => message,
+            };
+
+            dst.put::<Bytes>(message.into());
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum CodecError {
+    Io(std::io::Error),
+    Parse(MessageParseError),
+    Security(SecurityError),
+    Desync(Desync),
+}
+
+impl Display for CodecError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CodecError::Io(e) => write!(f, "i/o error: {}", e),
+            CodecError::Parse(e) => write!(f, "could not parse message: {:?}", e),
+            CodecError::Security(e) => write!(f, "could not decrypt message: {:?}", e),
+            CodecError::Desync(e) => write!(f, "error detection failed: {:?}", e),
+        }
+    }
+}
+
+impl std::error::Error for CodecError {}
+
+impl From<std::io::Error> for CodecError {
+    fn from(value: std::io::Error) -> Self {
+        CodecError::Io(value)
+    }
+}
+
+impl From<MessageParseError> for CodecError {
+    fn from(value: MessageParseError) -> Self {
+        CodecError::Parse(value)
+    }
+}
+
+impl From<SecurityError> for CodecError {
+    fn from(value: SecurityError) -> Self {
+        CodecError::Security(value)
+    }
+}
+
+impl From<Desync> for CodecError {
+    fn from(value: Desync) -> Self {
+        CodecError::Desync(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::net::message::Message;
+    use crate::net::message::MessageDirection::Req;
+    use crate::net::message::MessageKind::Game;
+    use crate::net::{CodecError, SilkroadCodec};
+    use crate::security::{Desync, SecurityBuilder};
+    use crate::test_support::KEY;
+    use bytes::{Bytes, BytesMut};
+    use tokio_util::codec::{Decoder, Encoder};
+
+    #[test]
+    fn decode_waits_for_whole_message() {
+        let message = Message::new(Req, Game, 1, Bytes::from_static(&[1, 2, 3]));
+        let wire: Bytes = message.clone().into();
+
+        let mut codec = SilkroadCodec::default();
+        let mut src = BytesMut::new();
+        for split in [2, 6, wire.len()] {
+            assert!(codec.decode(&mut src).unwrap().is_none());
+            src.extend_from_slice(&wire[src.len()..split]);
+        }
+
+        assert_eq!(codec.decode(&mut src).unwrap(), Some(message));
+        assert!(src.is_empty());
+    }
+
+    #[test]
+    fn roundtrip_with_security() {
+        let mut outbound = SilkroadCodec::new(
+            SecurityBuilder::default()
+                .blowfish(KEY)
+                .encoding_requirements((false, true))
+                .error_detection((0x1234, 0x5678))
+                .build(),
+        );
+        let mut inbound = SilkroadCodec::new(
+            SecurityBuilder::default()
+                .blowfish(KEY)
+                .encoding_requirements((true, false))
+                .error_detection((0x1234, 0x5678))
+                .build(),
+        );
+
+        let mut message = Message::new(Req, Game, 1, Bytes::from_static(&[1, 2, 3]));
+        message.header_mut().set_encrypted(true);
+
+        let mut wire = BytesMut::new();
+        outbound.encode(message.clone(), &mut wire).unwrap();
+        outbound.encode(message.clone(), &mut wire).unwrap();
+        let mut replay = wire.clone();
+
+        for _ in 0..2 {
+            let decoded = inbound.decode(&mut wire).unwrap().unwrap();
+            assert_eq!(decoded.reader(), message.clone().reader());
+        }
+
+        assert!(matches!(
+            inbound.decode(&mut replay),
+            Err(CodecError::Desync(Desync::Sequence { .. }))
+        ));
+    }
+}
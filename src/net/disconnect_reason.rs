@@ -0,0 +1,34 @@
+use crate::net::message::MessageParseError;
+use std::fmt::{Display, Formatter};
+use std::io::ErrorKind;
+
+/// Why a [NetConnection](crate::net::NetConnection) was closed
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum DisconnectReason {
+    /// The peer closed the stream
+    PeerClosed,
+
+    /// Reading from or writing to the stream failed
+    Io(ErrorKind),
+
+    /// The inbound byte stream could not be split into messages
+    Framing(MessageParseError),
+
+    /// An inbound message violated the installed [Security](crate::security::Security)
+    SecurityViolation,
+
+    /// The connection was closed or dropped locally
+    LocalClose,
+}
+
+impl Display for DisconnectReason {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DisconnectReason::PeerClosed => write!(f, "closed by peer"),
+            DisconnectReason::Io(kind) => write!(f, "i/o error ({})", kind),
+            DisconnectReason::Framing(e) => write!(f, "framing error ({:?})", e),
+            DisconnectReason::SecurityViolation => write!(f, "security violation"),
+            DisconnectReason::LocalClose => write!(f, "closed locally"),
+        }
+    }
+}
@@ -0,0 +1,11 @@
+pub use self::module_identification::{ModuleIdentification, ModuleIdentificationProcessor};
+mod module_identification;
+
+pub use self::ping::{NetPing, PingProcessor};
+mod ping;
+
+/// Operation of [ModuleIdentification] (`0x2001`)
+pub const MODULE_IDENTIFICATION: usize = 1;
+
+/// Operation of [NetPing] (`0x2002`)
+pub const PING: usize = 2;
@@ -0,0 +1,110 @@
+use crate::net::framework::MODULE_IDENTIFICATION;
+use crate::net::io::{BytesExtension, BytesMutExtension, Fragment, FragmentError};
+use crate::net::message::Message;
+use crate::net::message::MessageDirection::NoDir;
+use crate::net::message::MessageKind::Framework;
+use crate::net::{NetClient, Process};
+use bytes::{BufMut, Bytes, BytesMut};
+use log::{error, info};
+
+/// Tells the peer which module it is talking to (`0x2001`), e.g. `GatewayServer` or `SR_Client`
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ModuleIdentification {
+    pub name: String,
+    pub is_local: bool,
+}
+
+impl ModuleIdentification {
+    pub fn new(name: &str, is_local: bool) -> Self {
+        Self {
+            name: name.to_owned(),
+            is_local,
+        }
+    }
+}
+
+impl Fragment for ModuleIdentification {
+    fn get(reader: &mut Bytes) -> Result<Self, FragmentError> {
+        Ok(Self {
+            name: reader.get_string()?,
+            is_local: reader.read_u8("is local")? != 0,
+        })
+    }
+
+    fn put(&self, writer: &mut BytesMut) {
+        writer.put_string(&self.name);
+        writer.put_u8(self.is_local.into());
+    }
+}
+
+impl TryFrom<Message> for ModuleIdentification {
+    type Error = FragmentError;
+
+    fn try_from(m: Message) -> Result<Self, Self::Error> {
+        ModuleIdentification::decode(&m.reader())
+    }
+}
+
+impl From<ModuleIdentification> for Message {
+    fn from(value: ModuleIdentification) -> Self {
+        let mut data = BytesMut::new();
+        value.put(&mut data);
+        Message::new(NoDir, Framework, MODULE_IDENTIFICATION, data.freeze())
+    }
+}
+
+/// Identifies the [NetClient] with the module name its peer sends
+///
+/// Has to be registered for `Framework, 1, NoDir`. If it was created with
+/// [ModuleIdentificationProcessor::replying], it answers with its own identification.
+#[derive(Default)]
+pub struct ModuleIdentificationProcessor {
+    reply: Option<ModuleIdentification>,
+}
+
+impl ModuleIdentificationProcessor {
+    pub fn replying(identification: ModuleIdentification) -> Self {
+        Self {
+            reply: Some(identification),
+        }
+    }
+}
+
+impl Process for ModuleIdentificationProcessor {
+    fn process(&mut self, net_client: &mut NetClient, m: Message) {
+        let identification = match ModuleIdentification::try_from(m) {
+            Ok(identification) => identification,
+            Err(e) => {
+                error!("could not read module identification ({})", e);
+                return;
+            }
+        };
+
+        info!("identified as {}", identification.name);
+        net_client.identify(&identification.name);
+
+        if let Some(reply) = &self.reply {
+            net_client.send(reply.clone().into());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::net::framework::ModuleIdentification;
+    use crate::net::message::Message;
+    use bytes::Bytes;
+
+    #[test]
+    fn round_trips_identification() {
+        let identification = ModuleIdentification::new("GatewayServer", false);
+        let message: Message = identification.clone().into();
+
+        assert_eq!(u16::from(*message.header().id()), 0x2001);
+        assert_eq!(
+            message.clone().reader(),
+            Bytes::from_static(b"\x0D\x00GatewayServer\x00")
+        );
+        assert_eq!(ModuleIdentification::try_from(message), Ok(identification));
+    }
+}
@@ -0,0 +1,52 @@
+use crate::net::framework::PING;
+use crate::net::io::{Fragment, FragmentError};
+use crate::net::message::Message;
+use crate::net::message::MessageDirection::NoDir;
+use crate::net::message::MessageKind::Framework;
+use crate::net::{NetClient, Process};
+use bytes::{Bytes, BytesMut};
+
+/// Keeps an idle connection alive (`0x2002`), it has no payload
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct NetPing;
+
+impl Fragment for NetPing {
+    fn get(_reader: &mut Bytes) -> Result<Self, FragmentError> {
+        Ok(NetPing)
+    }
+
+    fn put(&self, _writer: &mut BytesMut) {}
+}
+
+impl From<NetPing> for Message {
+    fn from(_value: NetPing) -> Self {
+        Message::new(NoDir, Framework, PING, Bytes::new())
+    }
+}
+
+/// Answers every [NetPing] with a [NetPing]
+///
+/// Has to be registered for `Framework, 2, NoDir`. Only one side of a connection may answer
+/// pings, otherwise both keep pinging each other.
+#[derive(Default)]
+pub struct PingProcessor;
+
+impl Process for PingProcessor {
+    fn process(&mut self, net_client: &mut NetClient, _m: Message) {
+        net_client.send(NetPing.into());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::net::framework::NetPing;
+    use crate::net::message::Message;
+
+    #[test]
+    fn ping_is_empty() {
+        let message: Message = NetPing.into();
+
+        assert_eq!(u16::from(*message.header().id()), 0x2002);
+        assert!(message.reader().is_empty());
+    }
+}
@@ -1,5 +1,19 @@
+pub use self::deserializer::{from_bytes, from_bytes_with, Deserializer};
 pub use self::fragment::Fragment;
+pub use self::fragment_error::{FragmentError, FragmentErrorKind};
 pub use self::reader_extensions::BytesExtension;
+pub use self::serde_error::SerdeError;
+pub use self::serializer::{to_bytes, to_bytes_with, SeqSerializer, Serializer};
+pub use self::string_encoding::{EncodeError, StringEncoding};
+pub use self::wire_types::{Collection, U16List, Utf16String};
+pub use self::writer_extensions::BytesMutExtension;
 
+mod deserializer;
 mod fragment;
+mod fragment_error;
 mod reader_extensions;
+mod serde_error;
+mod serializer;
+mod string_encoding;
+mod wire_types;
+mod writer_extensions;
@@ -0,0 +1,466 @@
+use crate::net::io::wire_types::Layout;
+use crate::net::io::{
+    BytesExtension, FragmentError, FragmentErrorKind, SerdeError, StringEncoding,
+};
+use bytes::{Buf, Bytes};
+use serde::de::{
+    DeserializeOwned, DeserializeSeed, EnumAccess, IntoDeserializer, SeqAccess, VariantAccess,
+    Visitor,
+};
+
+/// Reads a `T` from `payload` in the Silkroad wire format, the whole payload has to be used.
+pub fn from_bytes<T: DeserializeOwned>(payload: Bytes) -> Result<T, SerdeError> {
+    from_bytes_with(payload, StringEncoding::default())
+}
+
+/// Like [from_bytes], with plain strings stored in `encoding`.
+pub fn from_bytes_with<T: DeserializeOwned>(
+    payload: Bytes,
+    encoding: StringEncoding,
+) -> Result<T, SerdeError> {
+    let mut deserializer = Deserializer::with_encoding(payload, encoding);
+    let value = T::deserialize(&mut deserializer).map_err(|e| deserializer.locate(e))?;
+
+    match deserializer.input.remaining() {
+        0 => Ok(value),
+        remaining => Err(SerdeError::TrailingBytes(remaining)),
+    }
+}
+
+/// A serde deserializer for the Silkroad wire format
+///
+/// The counterpart of [Serializer](crate::net::io::Serializer), the format is not
+/// self-describing, so `deserialize_any` and friends are not supported.
+pub struct Deserializer {
+    input: Bytes,
+    size: usize,
+    layout: Layout,
+    encoding: StringEncoding,
+}
+
+impl Deserializer {
+    pub fn new(input: Bytes) -> Self {
+        Deserializer::with_encoding(input, StringEncoding::default())
+    }
+
+    pub fn with_encoding(input: Bytes, encoding: StringEncoding) -> Self {
+        Self {
+            size: input.len(),
+            input,
+            layout: Layout::default(),
+            encoding,
+        }
+    }
+
+    /// The bytes that have not been read yet
+    pub fn remaining(&self) -> &Bytes {
+        &self.input
+    }
+
+    /// Adds the offset into the payload to errors raised while reading it.
+    fn locate(&self, error: SerdeError) -> SerdeError {
+        match error {
+            SerdeError::Fragment(e) if e.offset.is_none() => {
+                SerdeError::Fragment(e.within(self.size))
+            }
+            e => e,
+        }
+    }
+
+    fn take_layout(&mut self) -> Layout {
+        std::mem::take(&mut self.layout)
+    }
+
+    fn invalid(&self, field: &'static str, value: u64) -> SerdeError {
+        FragmentError::new(
+            field,
+            self.input.remaining(),
+            FragmentErrorKind::InvalidValue(value),
+        )
+        .into()
+    }
+
+    fn read_string(&mut self) -> Result<String, SerdeError> {
+        let encoding = match self.take_layout() {
+            Layout::Utf16 => StringEncoding::Utf16,
+            _ => self.encoding,
+        };
+
+        Ok(self.input.get_string_with(encoding)?)
+    }
+}
+
+/// Reads the items of a list, either a known number of them or until the collection ends
+struct SeqReader<'a> {
+    deserializer: &'a mut Deserializer,
+    /// `None` for collections
+    count: Option<usize>,
+}
+
+impl<'de> SeqAccess<'de> for SeqReader<'_> {
+    type Error = SerdeError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, SerdeError> {
+        match self.count.as_mut() {
+            Some(0) => return Ok(None),
+            Some(count) => *count -= 1,
+            None => match self.deserializer.input.read_u8("collection flag")? {
+                1 => {}
+                2 => return Ok(None),
+                flag => return Err(self.deserializer.invalid("collection flag", flag.into())),
+            },
+        }
+
+        seed.deserialize(&mut *self.deserializer).map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.count
+    }
+}
+
+impl<'de> EnumAccess<'de> for &mut Deserializer {
+    type Error = SerdeError;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Self), SerdeError> {
+        let index = self.input.read_u8("enum variant")? as u32;
+        let value = seed.deserialize(IntoDeserializer::<SerdeError>::into_deserializer(index))?;
+        Ok((value, self))
+    }
+}
+
+impl<'de> VariantAccess<'de> for &mut Deserializer {
+    type Error = SerdeError;
+
+    fn unit_variant(self) -> Result<(), SerdeError> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(
+        self,
+        seed: T,
+    ) -> Result<T::Value, SerdeError> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(
+        self,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        serde::Deserializer::deserialize_tuple(self, len, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        serde::Deserializer::deserialize_tuple(self, fields.len(), visitor)
+    }
+}
+
+impl<'de> serde::Deserializer<'de> for &mut Deserializer {
+    type Error = SerdeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, SerdeError> {
+        Err(SerdeError::Unsupported("self-describing data"))
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        match self.input.read_u8("bool")? {
+            0 => visitor.visit_bool(false),
+            1 => visitor.visit_bool(true),
+            value => Err(self.invalid("bool", value.into())),
+        }
+    }
+
+    fn deserialize_i8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        visitor.visit_i8(self.input.read_u8("i8")? as i8)
+    }
+
+    fn deserialize_i16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        visitor.visit_i16(self.input.read_u16("i16")? as i16)
+    }
+
+    fn deserialize_i32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        visitor.visit_i32(self.input.read_u32("i32")? as i32)
+    }
+
+    fn deserialize_i64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        visitor.visit_i64(self.input.read_u64("i64")? as i64)
+    }
+
+    fn deserialize_u8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        visitor.visit_u8(self.input.read_u8("u8")?)
+    }
+
+    fn deserialize_u16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        visitor.visit_u16(self.input.read_u16("u16")?)
+    }
+
+    fn deserialize_u32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        visitor.visit_u32(self.input.read_u32("u32")?)
+    }
+
+    fn deserialize_u64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        visitor.visit_u64(self.input.read_u64("u64")?)
+    }
+
+    fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        visitor.visit_f32(f32::from_bits(self.input.read_u32("f32")?))
+    }
+
+    fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        visitor.visit_f64(f64::from_bits(self.input.read_u64("f64")?))
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, SerdeError> {
+        Err(SerdeError::Unsupported("char"))
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        visitor.visit_string(self.read_string()?)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        visitor.visit_string(self.read_string()?)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        let len = self.input.read_u16("bytes length")? as usize;
+        visitor.visit_byte_buf(self.input.read_bytes("bytes", len)?.to_vec())
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        match self.input.read_u8("option flag")? {
+            0 => visitor.visit_none(),
+            1 => visitor.visit_some(self),
+            flag => Err(self.invalid("option flag", flag.into())),
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        if let Some(layout) = Layout::of(name) {
+            self.layout = layout;
+        }
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        let count = match self.take_layout() {
+            Layout::Collection => None,
+            Layout::U16List => Some(self.input.read_u16("list count")? as usize),
+            _ => Some(self.input.read_u8("list count")? as usize),
+        };
+
+        visitor.visit_seq(SeqReader {
+            deserializer: self,
+            count,
+        })
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        visitor.visit_seq(SeqReader {
+            deserializer: self,
+            count: Some(len),
+        })
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, SerdeError> {
+        Err(SerdeError::Unsupported("map"))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        self.deserialize_tuple(fields.len(), visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        visitor.visit_enum(self)
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, SerdeError> {
+        Err(SerdeError::Unsupported("identifier"))
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, SerdeError> {
+        Err(SerdeError::Unsupported("ignored value"))
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::net::io::{
+        from_bytes, from_bytes_with, to_bytes, to_bytes_with, Collection, EncodeError,
+        FragmentErrorKind, SerdeError, StringEncoding, U16List, Utf16String,
+    };
+    use bytes::Bytes;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Gender {
+        Male,
+        Female,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Item {
+        slot: u8,
+        ref_id: u32,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Character {
+        id: u32,
+        name: String,
+        nickname: Utf16String,
+        gender: Gender,
+        guild: Option<u32>,
+        level: u8,
+        items: Collection<Item>,
+        skills: Vec<u16>,
+        mastery: U16List<u32>,
+        position: (f32, f32),
+    }
+
+    fn character() -> Character {
+        Character {
+            id: 7,
+            name: String::from("Hero"),
+            nickname: Utf16String::from(String::from("Hé")),
+            gender: Gender::Female,
+            guild: None,
+            level: 42,
+            items: Collection(vec![Item { slot: 1, ref_id: 3 }]),
+            skills: vec![1, 2],
+            mastery: U16List(vec![9]),
+            position: (1.5, -2.0),
+        }
+    }
+
+    #[test]
+    fn writes_wire_conventions() {
+        let bytes = to_bytes(&character()).unwrap();
+
+        let expected: &[u8] = &[
+            7, 0, 0, 0, // id
+            4, 0, b'H', b'e', b'r', b'o', // name
+            2, 0, b'H', 0, 0xE9, 0,  // nickname
+            1,  // gender
+            0,  // guild
+            42, // level
+            1, 1, 3, 0, 0, 0, 2, // items
+            2, 1, 0, 2, 0, // skills
+            1, 0, 9, 0, 0, 0, // mastery
+            0, 0, 0xC0, 0x3F, 0, 0, 0, 0xC0, // position
+        ];
+        assert_eq!(bytes.as_ref(), expected);
+        assert_eq!(from_bytes::<Character>(bytes).unwrap(), character());
+    }
+
+    #[test]
+    fn reports_offset_of_truncated_payloads() {
+        let mut bytes = to_bytes(&character()).unwrap();
+        let truncated = bytes.split_to(8);
+
+        match from_bytes::<Character>(truncated) {
+            Err(SerdeError::Fragment(e)) => {
+                assert_eq!(e.field, "string");
+                assert_eq!(e.offset, Some(6));
+                assert_eq!(e.kind, FragmentErrorKind::Truncated { needed: 4 });
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn rejects_trailing_bytes_and_maps() {
+        assert_eq!(
+            from_bytes::<u16>(Bytes::from_static(&[1, 0, 0])),
+            Err(SerdeError::TrailingBytes(1))
+        );
+        assert_eq!(
+            to_bytes(&std::collections::HashMap::<u8, u8>::new()),
+            Err(SerdeError::Unsupported("map"))
+        );
+    }
+
+    #[test]
+    fn uses_the_default_string_encoding() {
+        let euc_kr = StringEncoding::CodePage(encoding_rs::EUC_KR);
+        let value = (
+            String::from("한국"),
+            Utf16String::from(String::from("한국")),
+        );
+
+        let bytes = to_bytes_with(&value, euc_kr).unwrap();
+        assert_eq!(
+            bytes.as_ref(),
+            &[4, 0, 0xC7, 0xD1, 0xB1, 0xB9, 2, 0, 0x5C, 0xD5, 0x6D, 0xAD]
+        );
+        assert_eq!(
+            from_bytes_with::<(String, Utf16String)>(bytes, euc_kr).unwrap(),
+            value
+        );
+
+        let windows_1252 = StringEncoding::CodePage(encoding_rs::WINDOWS_1252);
+        assert_eq!(
+            to_bytes_with(&value, windows_1252),
+            Err(SerdeError::Encode(EncodeError::Unmappable(windows_1252)))
+        );
+    }
+}
@@ -1,5 +1,16 @@
-use bytes::Bytes;
+use crate::net::io::FragmentError;
+use bytes::{Bytes, BytesMut};
 
-pub trait Fragment {
-    fn get(reader: &mut Bytes) -> Self;
+/// A typed part of a message payload that can be read from and written to the wire
+pub trait Fragment: Sized {
+    fn get(reader: &mut Bytes) -> Result<Self, FragmentError>;
+    fn put(&self, writer: &mut BytesMut);
+
+    /// Reads a fragment from the start of `payload`, errors carry their offset into it.
+    ///
+    /// Trailing bytes are left alone, use [Fragment::get] with a reader to keep on reading.
+    fn decode(payload: &Bytes) -> Result<Self, FragmentError> {
+        let mut reader = payload.clone();
+        Self::get(&mut reader).map_err(|e| e.within(payload.len()))
+    }
 }
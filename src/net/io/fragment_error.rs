@@ -0,0 +1,68 @@
+use crate::net::io::StringEncoding;
+use std::fmt::{Display, Formatter};
+
+/// What went wrong while decoding a [Fragment](crate::net::io::Fragment)
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum FragmentErrorKind {
+    /// The field needs more bytes than are remaining
+    Truncated { needed: usize },
+
+    /// A string field is not valid in the encoding it was read with
+    InvalidString(StringEncoding),
+
+    /// The field holds a value the protocol does not allow
+    InvalidValue(u64),
+}
+
+/// A [Fragment](crate::net::io::Fragment) could not be decoded
+///
+/// Readers only know the bytes that are `remaining` when a field fails, the `offset` into the
+/// payload is filled in by [Fragment::decode](crate::net::io::Fragment::decode).
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct FragmentError {
+    /// The field that was being read
+    pub field: &'static str,
+
+    /// Position of the field from the start of the decoded payload
+    pub offset: Option<usize>,
+
+    /// Bytes that were left when the field was read
+    pub remaining: usize,
+
+    pub kind: FragmentErrorKind,
+}
+
+impl FragmentError {
+    pub fn new(field: &'static str, remaining: usize, kind: FragmentErrorKind) -> Self {
+        Self {
+            field,
+            offset: None,
+            remaining,
+            kind,
+        }
+    }
+
+    /// Derives the offset from the size of the payload the decoding started on.
+    pub fn within(mut self, payload_size: usize) -> Self {
+        self.offset = Some(payload_size.saturating_sub(self.remaining));
+        self
+    }
+}
+
+impl Display for FragmentError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "could not read `{}`", self.field)?;
+        if let Some(offset) = self.offset {
+            write!(f, " at offset {}", offset)?;
+        }
+        write!(f, " with {} bytes remaining: ", self.remaining)?;
+
+        match self.kind {
+            FragmentErrorKind::Truncated { needed } => write!(f, "needs {} bytes", needed),
+            FragmentErrorKind::InvalidString(encoding) => write!(f, "invalid {}", encoding),
+            FragmentErrorKind::InvalidValue(value) => write!(f, "invalid value {}", value),
+        }
+    }
+}
+
+impl std::error::Error for FragmentError {}
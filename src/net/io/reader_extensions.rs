@@ -1,26 +1,222 @@
 use crate::net::io::fragment::Fragment;
+use crate::net::io::{FragmentError, FragmentErrorKind, StringEncoding};
 use bytes::{Buf, Bytes};
-use std::string::FromUtf8Error;
 
+/// Bounds checked reads, every method fails with a [FragmentError] instead of panicking when
+/// the buffer is too short.
+///
+/// `field` names what is being read, it only shows up in errors.
 pub trait BytesExtension {
-    fn get_collection<T: Fragment>(&mut self) -> Vec<T>;
-    fn get_string(&mut self) -> Result<String, FromUtf8Error>;
+    fn read_u8(&mut self, field: &'static str) -> Result<u8, FragmentError>;
+    fn read_u16(&mut self, field: &'static str) -> Result<u16, FragmentError>;
+    fn read_u32(&mut self, field: &'static str) -> Result<u32, FragmentError>;
+    fn read_u64(&mut self, field: &'static str) -> Result<u64, FragmentError>;
+    fn read_bytes(&mut self, field: &'static str, len: usize) -> Result<Bytes, FragmentError>;
+
+    fn get_collection<T: Fragment>(&mut self) -> Result<Vec<T>, FragmentError>;
+    fn get_u8_list<T: Fragment>(&mut self) -> Result<Vec<T>, FragmentError>;
+    fn get_u16_list<T: Fragment>(&mut self) -> Result<Vec<T>, FragmentError>;
+    fn get_string(&mut self) -> Result<String, FragmentError>;
+
+    /// Reads a `u16` length-prefixed string stored in `encoding`.
+    fn get_string_with(&mut self, encoding: StringEncoding) -> Result<String, FragmentError>;
 }
 
 impl BytesExtension for Bytes {
-    fn get_collection<T: Fragment>(&mut self) -> Vec<T> {
+    fn read_u8(&mut self, field: &'static str) -> Result<u8, FragmentError> {
+        ensure(self, field, 1).map(|_| self.get_u8())
+    }
+
+    fn read_u16(&mut self, field: &'static str) -> Result<u16, FragmentError> {
+        ensure(self, field, 2).map(|_| self.get_u16_le())
+    }
+
+    fn read_u32(&mut self, field: &'static str) -> Result<u32, FragmentError> {
+        ensure(self, field, 4).map(|_| self.get_u32_le())
+    }
+
+    fn read_u64(&mut self, field: &'static str) -> Result<u64, FragmentError> {
+        ensure(self, field, 8).map(|_| self.get_u64_le())
+    }
+
+    fn read_bytes(&mut self, field: &'static str, len: usize) -> Result<Bytes, FragmentError> {
+        ensure(self, field, len).map(|_| self.split_to(len))
+    }
+
+    fn get_collection<T: Fragment>(&mut self) -> Result<Vec<T>, FragmentError> {
         let mut entities = vec![];
-        while self.get_u8() == 1 {
-            entities.push(<T as Fragment>::get(self));
+        loop {
+            let remaining = self.remaining();
+            match self.read_u8("collection flag")? {
+                1 => entities.push(<T as Fragment>::get(self)?),
+                2 => return Ok(entities),
+                flag => {
+                    return Err(FragmentError::new(
+                        "collection flag",
+                        remaining,
+                        FragmentErrorKind::InvalidValue(flag.into()),
+                    ))
+                }
+            }
+        }
+    }
+
+    fn get_u8_list<T: Fragment>(&mut self) -> Result<Vec<T>, FragmentError> {
+        let count = self.read_u8("list count")?;
+        (0..count).map(|_| <T as Fragment>::get(self)).collect()
+    }
+
+    fn get_u16_list<T: Fragment>(&mut self) -> Result<Vec<T>, FragmentError> {
+        let count = self.read_u16("list count")?;
+        (0..count).map(|_| <T as Fragment>::get(self)).collect()
+    }
+
+    fn get_string(&mut self) -> Result<String, FragmentError> {
+        self.get_string_with(StringEncoding::Utf8)
+    }
+
+    fn get_string_with(&mut self, encoding: StringEncoding) -> Result<String, FragmentError> {
+        let len = self.read_u16("string length")? as usize;
+        let remaining = self.remaining();
+        let buf = self.read_bytes("string", len * encoding.unit_size())?;
+
+        encoding.decode(&buf).ok_or(FragmentError::new(
+            "string",
+            remaining,
+            FragmentErrorKind::InvalidString(encoding),
+        ))
+    }
+}
+
+fn ensure(reader: &Bytes, field: &'static str, needed: usize) -> Result<(), FragmentError> {
+    if reader.remaining() < needed {
+        Err(FragmentError::new(
+            field,
+            reader.remaining(),
+            FragmentErrorKind::Truncated { needed },
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::net::io::EncodeError;
+    use crate::net::io::{
+        BytesExtension, BytesMutExtension, Fragment, FragmentError, FragmentErrorKind,
+        StringEncoding,
+    };
+    use bytes::{BufMut, Bytes, BytesMut};
+
+    #[derive(Debug, PartialEq, Eq)]
+    struct Character {
+        id: u32,
+        name: String,
+    }
+
+    impl Fragment for Character {
+        fn get(reader: &mut Bytes) -> Result<Self, FragmentError> {
+            Ok(Self {
+                id: reader.read_u32("id")?,
+                name: reader.get_string()?,
+            })
+        }
+
+        fn put(&self, writer: &mut BytesMut) {
+            writer.put_u32_le(self.id);
+            writer.put_string(&self.name);
         }
+    }
+
+    #[test]
+    fn reports_offset_of_truncated_field() {
+        // id, length 4 but only "Ab"
+        let payload = Bytes::from_static(&[1, 0, 0, 0, 4, 0, b'A', b'b']);
+
+        let error = Character::decode(&payload).unwrap_err();
+        assert_eq!(
+            error,
+            FragmentError {
+                field: "string",
+                offset: Some(6),
+                remaining: 2,
+                kind: FragmentErrorKind::Truncated { needed: 4 },
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "could not read `string` at offset 6 with 2 bytes remaining: needs 4 bytes"
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_collection_flags_and_utf8() {
+        let mut reader = Bytes::from_static(&[1, 7, 0, 0, 0, 0, 0, 3]);
+        assert_eq!(
+            reader.get_collection::<Character>().unwrap_err().kind,
+            FragmentErrorKind::InvalidValue(3)
+        );
+
+        let mut reader = Bytes::from_static(&[1, 0, 0xFF]);
+        assert_eq!(
+            reader.get_string().unwrap_err().kind,
+            FragmentErrorKind::InvalidString(StringEncoding::Utf8)
+        );
+    }
+
+    #[test]
+    fn never_panics_on_empty_input() {
+        let mut reader = Bytes::new();
+        assert!(reader.read_u64("u64").is_err());
+        assert!(reader.get_u8_list::<Character>().is_err());
+        assert!(reader.get_u16_list::<Character>().is_err());
+        assert!(reader.get_collection::<Character>().is_err());
+        assert!(reader.get_string().is_err());
+    }
+
+    #[test]
+    fn round_trips_string_encodings() {
+        let encodings = [
+            (StringEncoding::Utf16, "Ünïcödé 한국"),
+            (StringEncoding::CodePage(encoding_rs::WINDOWS_1252), "Café"),
+            (StringEncoding::CodePage(encoding_rs::EUC_KR), "한국어"),
+        ];
+
+        for (encoding, value) in encodings {
+            let mut writer = BytesMut::new();
+            writer.put_string_with(value, encoding).unwrap();
+            assert_eq!(writer.freeze().get_string_with(encoding).unwrap(), value);
+        }
+
+        // "Hé" as windows-1252 is not valid UTF-8
+        let mut reader = Bytes::from_static(&[2, 0, b'H', 0xE9]);
+        assert_eq!(
+            reader.clone().get_string().unwrap_err().kind,
+            FragmentErrorKind::InvalidString(StringEncoding::Utf8)
+        );
+        assert_eq!(
+            reader
+                .get_string_with(StringEncoding::CodePage(encoding_rs::WINDOWS_1252))
+                .unwrap(),
+            "Hé"
+        );
 
-        entities
+        // UTF-16 lengths count code units
+        let mut writer = BytesMut::new();
+        writer.put_string_with("Hé", StringEncoding::Utf16).unwrap();
+        assert_eq!(writer.as_ref(), &[2, 0, b'H', 0, 0xE9, 0]);
     }
 
-    fn get_string(&mut self) -> Result<String, FromUtf8Error> {
-        let mut buf = vec![0u8; self.get_u16_le() as usize];
-        self.copy_to_slice(&mut buf);
+    #[test]
+    fn rejects_unmappable_characters() {
+        let mut writer = BytesMut::new();
+        let encoding = StringEncoding::CodePage(encoding_rs::WINDOWS_1252);
 
-        String::from_utf8(buf)
+        assert_eq!(
+            writer.put_string_with("한", encoding),
+            Err(EncodeError::Unmappable(encoding))
+        );
+        assert!(writer.is_empty());
     }
 }
@@ -0,0 +1,69 @@
+use crate::net::io::{EncodeError, FragmentError};
+use std::fmt::{Display, Formatter};
+
+/// A value could not be converted to or from the wire format
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum SerdeError {
+    /// Raised by a `Serialize` or `Deserialize` implementation
+    Custom(String),
+
+    /// Reading a value from the payload failed
+    Fragment(FragmentError),
+
+    /// The wire format has no encoding for the data type
+    Unsupported(&'static str),
+
+    /// A string could not be written in its encoding
+    Encode(EncodeError),
+
+    /// A list is too long for its length prefix
+    TooLong { len: usize, max: usize },
+
+    /// A list has to be prefixed with its length, but the length is not known up front
+    UnknownLength,
+
+    /// The value was read but the payload has bytes left
+    TrailingBytes(usize),
+}
+
+impl Display for SerdeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SerdeError::Custom(message) => write!(f, "{}", message),
+            SerdeError::Fragment(e) => write!(f, "{}", e),
+            SerdeError::Encode(e) => write!(f, "{}", e),
+            SerdeError::Unsupported(kind) => write!(f, "{} is not supported", kind),
+            SerdeError::TooLong { len, max } => {
+                write!(f, "length {} does not fit its prefix (max {})", len, max)
+            }
+            SerdeError::UnknownLength => write!(f, "list length has to be known up front"),
+            SerdeError::TrailingBytes(len) => write!(f, "{} trailing bytes", len),
+        }
+    }
+}
+
+impl std::error::Error for SerdeError {}
+
+impl serde::ser::Error for SerdeError {
+    fn custom<T: Display>(msg: T) -> Self {
+        SerdeError::Custom(msg.to_string())
+    }
+}
+
+impl serde::de::Error for SerdeError {
+    fn custom<T: Display>(msg: T) -> Self {
+        SerdeError::Custom(msg.to_string())
+    }
+}
+
+impl From<FragmentError> for SerdeError {
+    fn from(value: FragmentError) -> Self {
+        SerdeError::Fragment(value)
+    }
+}
+
+impl From<EncodeError> for SerdeError {
+    fn from(value: EncodeError) -> Self {
+        SerdeError::Encode(value)
+    }
+}
@@ -0,0 +1,381 @@
+use crate::net::io::wire_types::Layout;
+use crate::net::io::{BytesMutExtension, SerdeError, StringEncoding};
+use bytes::{BufMut, Bytes, BytesMut};
+use serde::ser::{
+    SerializeMap, SerializeSeq, SerializeStruct, SerializeStructVariant, SerializeTuple,
+    SerializeTupleStruct, SerializeTupleVariant,
+};
+use serde::Serialize;
+
+/// Writes `value` in the Silkroad wire format.
+pub fn to_bytes<T: Serialize + ?Sized>(value: &T) -> Result<Bytes, SerdeError> {
+    to_bytes_with(value, StringEncoding::default())
+}
+
+/// Writes `value` in the Silkroad wire format, with plain strings stored in `encoding`.
+pub fn to_bytes_with<T: Serialize + ?Sized>(
+    value: &T,
+    encoding: StringEncoding,
+) -> Result<Bytes, SerdeError> {
+    let mut serializer = Serializer::with_encoding(encoding);
+    value.serialize(&mut serializer)?;
+    Ok(serializer.into_inner())
+}
+
+/// A serde serializer for the Silkroad wire format
+///
+/// - integers and floats are little-endian, `bool` is a single byte
+/// - `String`s are prefixed with their `u16` length and stored in the serializer's
+///   [StringEncoding], [Utf16String](crate::net::io::Utf16String) is always UTF-16
+/// - `Vec`s are prefixed with their `u8` item count, see [U16List](crate::net::io::U16List) and
+///   [Collection](crate::net::io::Collection) for the other list encodings
+/// - `Option`s are prefixed with `0` or `1`
+/// - structs and tuples are written field by field, enums as their `u8` variant index followed
+///   by the variant's fields
+#[derive(Default)]
+pub struct Serializer {
+    output: BytesMut,
+    layout: Layout,
+    encoding: StringEncoding,
+}
+
+impl Serializer {
+    pub fn with_encoding(encoding: StringEncoding) -> Self {
+        Self {
+            encoding,
+            ..Default::default()
+        }
+    }
+
+    pub fn into_inner(self) -> Bytes {
+        self.output.freeze()
+    }
+
+    fn take_layout(&mut self) -> Layout {
+        std::mem::take(&mut self.layout)
+    }
+
+    fn put_u8_len(&mut self, len: usize) -> Result<(), SerdeError> {
+        let len = u8::try_from(len).map_err(|_| SerdeError::TooLong {
+            len,
+            max: u8::MAX as usize,
+        })?;
+        self.output.put_u8(len);
+        Ok(())
+    }
+
+    fn put_u16_len(&mut self, len: usize) -> Result<(), SerdeError> {
+        let len = u16::try_from(len).map_err(|_| SerdeError::TooLong {
+            len,
+            max: u16::MAX as usize,
+        })?;
+        self.output.put_u16_le(len);
+        Ok(())
+    }
+}
+
+/// Serializes the items of a list, writing the collection markers if needed
+pub struct SeqSerializer<'a> {
+    serializer: &'a mut Serializer,
+    collection: bool,
+}
+
+impl<'a> serde::Serializer for &'a mut Serializer {
+    type Ok = ();
+    type Error = SerdeError;
+    type SerializeSeq = SeqSerializer<'a>;
+    type SerializeTuple = Self;
+    type SerializeTupleStruct = Self;
+    type SerializeTupleVariant = Self;
+    type SerializeMap = Self;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Self;
+
+    fn serialize_bool(self, v: bool) -> Result<(), SerdeError> {
+        self.output.put_u8(v.into());
+        Ok(())
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<(), SerdeError> {
+        self.output.put_i8(v);
+        Ok(())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<(), SerdeError> {
+        self.output.put_i16_le(v);
+        Ok(())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<(), SerdeError> {
+        self.output.put_i32_le(v);
+        Ok(())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<(), SerdeError> {
+        self.output.put_i64_le(v);
+        Ok(())
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<(), SerdeError> {
+        self.output.put_u8(v);
+        Ok(())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<(), SerdeError> {
+        self.output.put_u16_le(v);
+        Ok(())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<(), SerdeError> {
+        self.output.put_u32_le(v);
+        Ok(())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<(), SerdeError> {
+        self.output.put_u64_le(v);
+        Ok(())
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<(), SerdeError> {
+        self.output.put_f32_le(v);
+        Ok(())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<(), SerdeError> {
+        self.output.put_f64_le(v);
+        Ok(())
+    }
+
+    fn serialize_char(self, _v: char) -> Result<(), SerdeError> {
+        Err(SerdeError::Unsupported("char"))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), SerdeError> {
+        let encoding = match self.take_layout() {
+            Layout::Utf16 => StringEncoding::Utf16,
+            _ => self.encoding,
+        };
+
+        Ok(self.output.put_string_with(v, encoding)?)
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), SerdeError> {
+        self.put_u16_len(v.len())?;
+        self.output.put_slice(v);
+        Ok(())
+    }
+
+    fn serialize_none(self) -> Result<(), SerdeError> {
+        self.output.put_u8(0);
+        Ok(())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), SerdeError> {
+        self.output.put_u8(1);
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), SerdeError> {
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), SerdeError> {
+        Ok(())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<(), SerdeError> {
+        self.put_u8_len(variant_index as usize)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<(), SerdeError> {
+        if let Some(layout) = Layout::of(name) {
+            self.layout = layout;
+        }
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        value: &T,
+    ) -> Result<(), SerdeError> {
+        self.put_u8_len(variant_index as usize)?;
+        value.serialize(self)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SeqSerializer<'a>, SerdeError> {
+        let layout = self.take_layout();
+        match layout {
+            Layout::Collection => {}
+            Layout::U16List => self.put_u16_len(len.ok_or(SerdeError::UnknownLength)?)?,
+            _ => self.put_u8_len(len.ok_or(SerdeError::UnknownLength)?)?,
+        }
+
+        Ok(SeqSerializer {
+            serializer: self,
+            collection: layout == Layout::Collection,
+        })
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self, SerdeError> {
+        Ok(self)
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Self, SerdeError> {
+        Ok(self)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self, SerdeError> {
+        self.put_u8_len(variant_index as usize)?;
+        Ok(self)
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self, SerdeError> {
+        Err(SerdeError::Unsupported("map"))
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self, SerdeError> {
+        Ok(self)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self, SerdeError> {
+        self.put_u8_len(variant_index as usize)?;
+        Ok(self)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+impl SerializeSeq for SeqSerializer<'_> {
+    type Ok = ();
+    type Error = SerdeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        if self.collection {
+            self.serializer.output.put_u8(1);
+        }
+        value.serialize(&mut *self.serializer)
+    }
+
+    fn end(self) -> Result<(), SerdeError> {
+        if self.collection {
+            self.serializer.output.put_u8(2);
+        }
+        Ok(())
+    }
+}
+
+impl SerializeTuple for &mut Serializer {
+    type Ok = ();
+    type Error = SerdeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), SerdeError> {
+        Ok(())
+    }
+}
+
+impl SerializeTupleStruct for &mut Serializer {
+    type Ok = ();
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), SerdeError> {
+        Ok(())
+    }
+}
+
+impl SerializeTupleVariant for &mut Serializer {
+    type Ok = ();
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), SerdeError> {
+        Ok(())
+    }
+}
+
+impl SerializeMap for &mut Serializer {
+    type Ok = ();
+    type Error = SerdeError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, _key: &T) -> Result<(), SerdeError> {
+        Err(SerdeError::Unsupported("map"))
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, _value: &T) -> Result<(), SerdeError> {
+        Err(SerdeError::Unsupported("map"))
+    }
+
+    fn end(self) -> Result<(), SerdeError> {
+        Err(SerdeError::Unsupported("map"))
+    }
+}
+
+impl SerializeStruct for &mut Serializer {
+    type Ok = ();
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        _key: &'static str,
+        value: &T,
+    ) -> Result<(), SerdeError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), SerdeError> {
+        Ok(())
+    }
+}
+
+impl SerializeStructVariant for &mut Serializer {
+    type Ok = ();
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        _key: &'static str,
+        value: &T,
+    ) -> Result<(), SerdeError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), SerdeError> {
+        Ok(())
+    }
+}
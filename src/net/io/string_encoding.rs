@@ -0,0 +1,97 @@
+use encoding_rs::Encoding;
+use std::fmt::{Display, Formatter};
+
+/// How the characters of a `u16` length-prefixed string are stored
+///
+/// The length prefix counts code units, so it is the number of bytes for every encoding but
+/// [StringEncoding::Utf16], where it is the number of UTF-16 code units.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum StringEncoding {
+    #[default]
+    Utf8,
+
+    /// UTF-16LE
+    Utf16,
+
+    /// A legacy single or multi byte code page, e.g. [encoding_rs::WINDOWS_1252] or
+    /// [encoding_rs::EUC_KR]
+    ///
+    /// Use [StringEncoding::Utf16] for UTF-16, code pages are only decoded and encoded as bytes.
+    CodePage(&'static Encoding),
+}
+
+impl StringEncoding {
+    /// Bytes per unit counted by the length prefix
+    pub fn unit_size(&self) -> usize {
+        match self {
+            StringEncoding::Utf16 => 2,
+            _ => 1,
+        }
+    }
+
+    /// Decodes `data`, `None` if it is not valid in this encoding.
+    pub fn decode(&self, data: &[u8]) -> Option<String> {
+        match self {
+            StringEncoding::Utf8 => String::from_utf8(data.to_vec()).ok(),
+            StringEncoding::Utf16 => {
+                let units: Vec<u16> = data
+                    .chunks_exact(2)
+                    .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+                    .collect();
+                String::from_utf16(&units).ok()
+            }
+            StringEncoding::CodePage(encoding) => encoding
+                .decode_without_bom_handling_and_without_replacement(data)
+                .map(String::from),
+        }
+    }
+
+    /// Encodes `value`, `None` if a character can not be represented in this encoding.
+    pub fn encode(&self, value: &str) -> Option<Vec<u8>> {
+        match self {
+            StringEncoding::Utf8 => Some(value.as_bytes().to_vec()),
+            StringEncoding::Utf16 => {
+                Some(value.encode_utf16().flat_map(u16::to_le_bytes).collect())
+            }
+            StringEncoding::CodePage(encoding) => {
+                let (data, used, unmappable) = encoding.encode(value);
+                (used == *encoding && !unmappable).then(|| data.into_owned())
+            }
+        }
+    }
+}
+
+impl Display for StringEncoding {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StringEncoding::Utf8 => write!(f, "UTF-8"),
+            StringEncoding::Utf16 => write!(f, "UTF-16LE"),
+            StringEncoding::CodePage(encoding) => write!(f, "{}", encoding.name()),
+        }
+    }
+}
+
+/// A string could not be written with the requested [StringEncoding]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum EncodeError {
+    /// The string contains a character the encoding can not represent
+    Unmappable(StringEncoding),
+
+    /// The encoded string has more units than its `u16` length prefix can count
+    TooLong { len: usize, max: usize },
+}
+
+impl Display for EncodeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EncodeError::Unmappable(encoding) => {
+                write!(f, "string can not be represented in {}", encoding)
+            }
+            EncodeError::TooLong { len, max } => {
+                write!(f, "string length {} exceeds {}", len, max)
+            }
+        }
+    }
+}
+
+impl std::error::Error for EncodeError {}
@@ -0,0 +1,66 @@
+use serde::{Deserialize, Serialize};
+
+pub(crate) const UTF16_STRING: &str = "$silkrust::Utf16String";
+pub(crate) const COLLECTION: &str = "$silkrust::Collection";
+pub(crate) const U16_LIST: &str = "$silkrust::U16List";
+
+/// A string written as its `u16` count of UTF-16 code units, followed by the UTF-16LE code units
+///
+/// Plain `String` fields are written as `u16` length-prefixed single-byte strings.
+#[derive(Debug, PartialEq, Eq, Clone, Default, Serialize, Deserialize)]
+#[serde(rename = "$silkrust::Utf16String")]
+pub struct Utf16String(pub String);
+
+/// A list written as `1` followed by the item for every item, terminated by `2`
+///
+/// See [BytesExtension::get_collection](crate::net::io::BytesExtension::get_collection).
+#[derive(Debug, PartialEq, Eq, Clone, Default, Serialize, Deserialize)]
+#[serde(rename = "$silkrust::Collection")]
+pub struct Collection<T>(pub Vec<T>);
+
+/// A list prefixed with its `u16` item count
+///
+/// Plain `Vec` fields are prefixed with a `u8` item count.
+#[derive(Debug, PartialEq, Eq, Clone, Default, Serialize, Deserialize)]
+#[serde(rename = "$silkrust::U16List")]
+pub struct U16List<T>(pub Vec<T>);
+
+impl From<String> for Utf16String {
+    fn from(value: String) -> Self {
+        Self(value)
+    }
+}
+
+impl<T> From<Vec<T>> for Collection<T> {
+    fn from(value: Vec<T>) -> Self {
+        Self(value)
+    }
+}
+
+impl<T> From<Vec<T>> for U16List<T> {
+    fn from(value: Vec<T>) -> Self {
+        Self(value)
+    }
+}
+
+/// How the next string or list is laid out, selected by the wrapper types
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub(crate) enum Layout {
+    #[default]
+    Plain,
+    Utf16,
+    Collection,
+    U16List,
+}
+
+impl Layout {
+    /// The layout selected by the newtype struct `name`, if it is one of the wrapper types
+    pub(crate) fn of(name: &str) -> Option<Layout> {
+        match name {
+            UTF16_STRING => Some(Layout::Utf16),
+            COLLECTION => Some(Layout::Collection),
+            U16_LIST => Some(Layout::U16List),
+            _ => None,
+        }
+    }
+}
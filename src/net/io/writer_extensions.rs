@@ -0,0 +1,159 @@
+use crate::net::io::fragment::Fragment;
+use crate::net::io::{EncodeError, StringEncoding};
+use bytes::{BufMut, BytesMut};
+
+/// Marks another entry of a collection
+const COLLECTION_ENTRY: u8 = 1;
+
+/// Marks the end of a collection
+const COLLECTION_END: u8 = 2;
+
+/// The write side of [BytesExtension](crate::net::io::BytesExtension)
+pub trait BytesMutExtension {
+    /// Writes every item prefixed with `1`, followed by a terminating `2`.
+    fn put_collection<T: Fragment>(&mut self, items: &[T]);
+
+    /// Writes the item count as `u8`, followed by the items.
+    ///
+    /// Panics if there are more than `u8::MAX` items.
+    fn put_u8_list<T: Fragment>(&mut self, items: &[T]);
+
+    /// Writes the item count as `u16`, followed by the items.
+    ///
+    /// Panics if there are more than `u16::MAX` items.
+    fn put_u16_list<T: Fragment>(&mut self, items: &[T]);
+
+    /// Writes the length of `value` in bytes as `u16`, followed by its bytes.
+    ///
+    /// Panics if `value` is longer than `u16::MAX` bytes.
+    fn put_string(&mut self, value: &str);
+
+    /// Writes the length of `value` in code units of `encoding` as `u16`, followed by the
+    /// encoded string. Nothing is written if it fails.
+    fn put_string_with(&mut self, value: &str, encoding: StringEncoding)
+        -> Result<(), EncodeError>;
+
+    /// Writes `fragment`, the counterpart of [Fragment::get].
+    fn put_fragment<T: Fragment>(&mut self, fragment: &T);
+}
+
+impl BytesMutExtension for BytesMut {
+    fn put_collection<T: Fragment>(&mut self, items: &[T]) {
+        for item in items {
+            self.put_u8(COLLECTION_ENTRY);
+            item.put(self);
+        }
+
+        self.put_u8(COLLECTION_END);
+    }
+
+    fn put_u8_list<T: Fragment>(&mut self, items: &[T]) {
+        self.put_u8(u8::try_from(items.len()).expect("list too long for u8 count"));
+        items.iter().for_each(|item| item.put(self));
+    }
+
+    fn put_u16_list<T: Fragment>(&mut self, items: &[T]) {
+        self.put_u16_le(u16::try_from(items.len()).expect("list too long for u16 count"));
+        items.iter().for_each(|item| item.put(self));
+    }
+
+    fn put_string(&mut self, value: &str) {
+        self.put_string_with(value, StringEncoding::Utf8)
+            .expect("string too long for u16 length");
+    }
+
+    fn put_string_with(
+        &mut self,
+        value: &str,
+        encoding: StringEncoding,
+    ) -> Result<(), EncodeError> {
+        let data = encoding
+            .encode(value)
+            .ok_or(EncodeError::Unmappable(encoding))?;
+        let len = data.len() / encoding.unit_size();
+
+        let len = u16::try_from(len).map_err(|_| EncodeError::TooLong {
+            len,
+            max: u16::MAX as usize,
+        })?;
+        self.put_u16_le(len);
+        self.put_slice(&data);
+        Ok(())
+    }
+
+    fn put_fragment<T: Fragment>(&mut self, fragment: &T) {
+        fragment.put(self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::net::io::{BytesExtension, BytesMutExtension, Fragment, FragmentError};
+    use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+    #[derive(Debug, PartialEq, Eq)]
+    struct Item {
+        id: u32,
+        name: String,
+    }
+
+    impl Fragment for Item {
+        fn get(reader: &mut Bytes) -> Result<Self, FragmentError> {
+            Ok(Self {
+                id: reader.read_u32("id")?,
+                name: reader.get_string()?,
+            })
+        }
+
+        fn put(&self, writer: &mut BytesMut) {
+            writer.put_u32_le(self.id);
+            writer.put_string(&self.name);
+        }
+    }
+
+    fn items() -> Vec<Item> {
+        vec![
+            Item {
+                id: 1,
+                name: String::from("Blade"),
+            },
+            Item {
+                id: 2,
+                name: String::from("Shield"),
+            },
+        ]
+    }
+
+    #[test]
+    fn writes_strings() {
+        let mut writer = BytesMut::new();
+        writer.put_string("Gateway");
+
+        assert_eq!(writer.as_ref(), b"\x07\x00Gateway");
+        assert_eq!(writer.freeze().get_string().unwrap(), "Gateway");
+    }
+
+    #[test]
+    fn round_trips_collections() {
+        let mut writer = BytesMut::new();
+        writer.put_collection(&items());
+        writer.put_u8_list(&items());
+        writer.put_u16_list(&items());
+        writer.put_fragment(&items()[0]);
+
+        let mut reader = writer.freeze();
+        assert_eq!(reader.get_collection::<Item>().unwrap(), items());
+        assert_eq!(reader.get_u8_list::<Item>().unwrap(), items());
+        assert_eq!(reader.get_u16_list::<Item>().unwrap(), items());
+        assert_eq!(Item::get(&mut reader).unwrap(), items().remove(0));
+        assert!(!reader.has_remaining());
+    }
+
+    #[test]
+    fn terminates_empty_collections() {
+        let mut writer = BytesMut::new();
+        writer.put_collection::<Item>(&[]);
+
+        assert_eq!(writer.as_ref(), &[2]);
+    }
+}
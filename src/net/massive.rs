@@ -1,46 +1,59 @@
-use crate::net::message::{Header, Message, MessageId};
-use crate::net::{NetClient, Process};
+use crate::net::message::MessageDirection::Req;
+use crate::net::message::MessageKind::Framework;
+use crate::net::message::{Message, MessageId, MessageParseError, HEADER_SIZE, MAX_MESSAGE_SIZE};
+use crate::net::DEFAULT_MAX_MASSIVE_SIZE;
+use crate::security::blowfish_compat::BLOCK_SIZE;
 use bytes::{Buf, BufMut, Bytes, BytesMut};
-use log::{error, trace};
+use log::{trace, warn};
 
-#[derive(Default)]
-pub struct MassiveProcessor {
-    buffer: MassiveBuffer,
+/// Operation of the Framework message that carries massive fragments (`0x600D`)
+pub(crate) const MASSIVE_OPERATION: usize = 0xD;
+
+/// Whether `id` is the id of a massive fragment
+pub(crate) fn is_massive(id: &MessageId) -> bool {
+    id.direction() == Req && id.kind() == Framework && id.operation() == MASSIVE_OPERATION
 }
 
-impl Process for MassiveProcessor {
-    fn process(&mut self, net_client: &mut NetClient, m: Message) {
-        let collected = match self.buffer.add(m) {
-            Ok(_) => self.buffer.collect(),
-            Err(e) => {
-                error!("could not add massive message to buffer! ({:?})", e);
-                None
-            }
-        };
+/// Largest chunk of data a single body fragment carries.
+///
+/// Leaves room for the body flag and for the block padding, should the message be encrypted.
+const MAX_BODY_SIZE: usize = MAX_MESSAGE_SIZE - HEADER_SIZE - BLOCK_SIZE - 1;
 
-        if let Some(message) = collected {
-            net_client.receive(message);
-        }
-    }
-}
+/// Largest message [split] accepts, the massive header counts the body fragments in 16 bits
+const MAX_SPLIT_SIZE: usize = u16::MAX as usize * MAX_BODY_SIZE;
 
-#[derive(Default)]
 pub(crate) struct MassiveBuffer {
     header: Option<MassiveHeader>,
     count: usize,
     data: BytesMut,
+    max_size: usize,
+}
+
+impl Default for MassiveBuffer {
+    fn default() -> Self {
+        Self {
+            header: None,
+            count: 0,
+            data: BytesMut::new(),
+            max_size: DEFAULT_MAX_MASSIVE_SIZE,
+        }
+    }
 }
 
 #[derive(Debug)]
 pub(crate) enum MassiveError {
-    /// Cannot add a Header [Message] to the buffer as it has already been initialized
-    AlreadyInitialized,
+    /// The [Message] is too short to hold a massive fragment
+    Malformed,
 
     /// Cannot add a Body [Message] to the buffer as it requires an initial header message first
     HeaderMissing,
 
     /// Cannot add another Body [Message] to the buffer as the buffer capacity has been reached.
     TooMany,
+
+    /// Cannot add another Body [Message] to the buffer as the reassembled message would exceed
+    /// the maximum size
+    TooLarge,
 }
 
 enum MassiveMessage {
@@ -57,23 +70,99 @@ struct MassiveBody {
     data: Bytes,
 }
 
-impl From<Bytes> for MassiveMessage {
-    fn from(mut value: Bytes) -> Self {
+impl From<MassiveMessage> for Bytes {
+    fn from(value: MassiveMessage) -> Self {
+        let mut mem = BytesMut::new();
+        match value {
+            MassiveMessage::Header(header) => {
+                mem.put_u8(1);
+                mem.put_u16_le(header.total_count as u16);
+                mem.put_u16_le(header.id.into());
+            }
+            MassiveMessage::Body(body) => {
+                mem.put_u8(0);
+                mem.put_slice(&body.data);
+            }
+        }
+
+        mem.freeze()
+    }
+}
+
+impl TryFrom<Bytes> for MassiveMessage {
+    type Error = MassiveError;
+
+    fn try_from(mut value: Bytes) -> Result<Self, Self::Error> {
+        if !value.has_remaining() {
+            return Err(MassiveError::Malformed);
+        }
+
         if value.get_u8() == 1 {
-            MassiveMessage::Header(MassiveHeader {
+            if value.remaining() < 4 {
+                return Err(MassiveError::Malformed);
+            }
+
+            Ok(MassiveMessage::Header(MassiveHeader {
                 total_count: value.get_u16_le() as usize,
                 id: MessageId::from(value.get_u16_le()),
-            })
+            }))
         } else {
-            MassiveMessage::Body(MassiveBody { data: value })
+            Ok(MassiveMessage::Body(MassiveBody { data: value }))
         }
     }
 }
 
+/// Splits a [Message] that does not fit into [MAX_MESSAGE_SIZE] into a massive header followed
+/// by as many body fragments as needed. Messages that fit are returned as they are.
+///
+/// All fragments inherit the encryption flag of the original message. Messages that would need
+/// more body fragments than the massive header can count fail with
+/// [MessageParseError::Oversized].
+pub(crate) fn split(message: Message) -> Result<Vec<Message>, MessageParseError> {
+    let id = *message.header().id();
+    let encrypted = message.is_encrypted();
+    let mut data = message.clone().reader();
+
+    if data.len() <= MAX_BODY_SIZE {
+        return Ok(vec![message]);
+    }
+    if data.len() > MAX_SPLIT_SIZE {
+        return Err(MessageParseError::Oversized {
+            size: data.len(),
+            max: MAX_SPLIT_SIZE,
+        });
+    }
+
+    let total_count = data.len().div_ceil(MAX_BODY_SIZE);
+    let mut fragments = Vec::with_capacity(total_count + 1);
+    fragments.push(MassiveMessage::Header(MassiveHeader { total_count, id }));
+    while data.has_remaining() {
+        let data = data.split_to(data.len().min(MAX_BODY_SIZE));
+        fragments.push(MassiveMessage::Body(MassiveBody { data }));
+    }
+
+    trace!("split {} into {} massive fragments", id, total_count);
+
+    Ok(fragments
+        .into_iter()
+        .map(|fragment| {
+            let mut message = Message::new(Req, Framework, MASSIVE_OPERATION, fragment.into());
+            message.header_mut().set_encrypted(encrypted);
+            message
+        })
+        .collect())
+}
+
 impl MassiveBuffer {
+    /// Adds a massive fragment, the buffer is reset if the fragment cannot be parsed.
     pub fn add(&mut self, message: Message) -> Result<(), MassiveError> {
-        let reader = message.reader();
-        let massive = MassiveMessage::from(reader);
+        let massive = match MassiveMessage::try_from(message.reader()) {
+            Ok(massive) => massive,
+            Err(e) => {
+                self.reset();
+                return Err(e);
+            }
+        };
 
         match massive {
             MassiveMessage::Header(header) => self.add_header(header),
@@ -90,15 +179,22 @@ impl MassiveBuffer {
 
             trace!("massive collected into: {}", header.id);
 
-            Some(Message::from((
-                Header::new(header.id, data.len() as u16),
+            Some(Message::new(
+                header.id.direction(),
+                header.id.kind(),
+                header.id.operation(),
                 data.freeze(),
-            )))
+            ))
         } else {
             None
         }
     }
 
+    /// Sets the largest message reassembled, defaults to [DEFAULT_MAX_MASSIVE_SIZE]
+    pub fn set_max_size(&mut self, max_size: usize) {
+        self.max_size = max_size;
+    }
+
     fn reset(&mut self) {
         self.header = None;
         self.count = 0;
@@ -106,19 +202,30 @@ impl MassiveBuffer {
     }
 
     fn add_header(&mut self, header: MassiveHeader) -> Result<(), MassiveError> {
-        if let Some(_) = self.header {
-            Err(MassiveError::AlreadyInitialized)
-        } else {
-            trace!("initialized massive buffer for id {}", header.id);
-            self.header = Some(header);
-            Ok(())
+        if let Some(previous) = self.header {
+            warn!(
+                "discarded incomplete massive for id {} ({}/{} bodies)",
+                previous.id, self.count, previous.total_count
+            );
+            self.reset();
         }
+
+        trace!("initialized massive buffer for id {}", header.id);
+        self.header = Some(header);
+        Ok(())
     }
 
     fn add_body(&mut self, body: MassiveBody) -> Result<(), MassiveError> {
         let header = self.header.as_mut().ok_or(MassiveError::HeaderMissing)?;
         if header.total_count < self.count + 1 {
             Err(MassiveError::TooMany)
+        } else if self.data.len() + body.data.len() > self.max_size {
+            warn!(
+                "discarded massive for id {} exceeding {} bytes",
+                header.id, self.max_size
+            );
+            self.reset();
+            Err(MassiveError::TooLarge)
         } else {
             self.count += 1;
             self.data.put_slice(&body.data);
@@ -127,3 +234,142 @@ impl MassiveBuffer {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::net::massive::{
+        split, MassiveBuffer, MassiveError, MASSIVE_OPERATION, MAX_BODY_SIZE, MAX_SPLIT_SIZE,
+    };
+    use crate::net::message::MessageDirection::{Ack, Req};
+    use crate::net::message::MessageKind::{Framework, Game};
+    use crate::net::message::{Message, MessageId, MessageParseError, MAX_MESSAGE_SIZE};
+    use bytes::Bytes;
+
+    #[test]
+    fn small_messages_are_not_split() {
+        let message = Message::new(Ack, Game, 1, Bytes::from(vec![7u8; MAX_BODY_SIZE]));
+        assert_eq!(split(message.clone()).unwrap(), vec![message]);
+    }
+
+    #[test]
+    fn split_fragments_are_collected_into_original() {
+        let data: Vec<u8> = (0..10_000u32).map(|i| i as u8).collect();
+        let message = Message::new(Ack, Game, 1, Bytes::from(data));
+
+        let fragments = split(message.clone()).unwrap();
+        assert_eq!(fragments.len(), 1 + 3);
+
+        let mut buffer = MassiveBuffer::default();
+        for fragment in fragments {
+            assert_eq!(*fragment.header().id(), MessageId::from(0x600D));
+            assert!(fragment.header().message_size() as usize <= MAX_MESSAGE_SIZE);
+            buffer.add(fragment).unwrap();
+        }
+
+        assert_eq!(buffer.collect(), Some(message));
+    }
+
+    #[test]
+    fn large_messages_keep_their_encryption_flag() {
+        let data: Vec<u8> = (0..40 * 1024u32).map(|i| i as u8).collect();
+        let message = Message::new(Ack, Game, 1, Bytes::from(data.clone()));
+        assert!(!message.is_encrypted());
+
+        let fragments = split(message.clone()).unwrap();
+        assert!(fragments.iter().all(|fragment| !fragment.is_encrypted()));
+
+        let mut buffer = MassiveBuffer::default();
+        for fragment in fragments {
+            buffer.add(fragment).unwrap();
+        }
+
+        assert_eq!(buffer.collect(), Some(message));
+
+        let mut message = Message::new(Ack, Game, 1, Bytes::from(data));
+        message.header_mut().set_encrypted(true);
+        assert!(split(message).unwrap().iter().all(Message::is_encrypted));
+    }
+
+    #[test]
+    fn split_rejects_more_fragments_than_the_header_counts() {
+        let message = Message::new(Ack, Game, 1, Bytes::from(vec![0u8; MAX_SPLIT_SIZE + 1]));
+        assert!(matches!(
+            split(message),
+            Err(MessageParseError::Oversized {
+                max: MAX_SPLIT_SIZE,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn oversized_massive_resets_the_buffer() {
+        let message = Message::new(Ack, Game, 1, Bytes::from(vec![7u8; 10_000]));
+        let fragments = split(message.clone()).unwrap();
+
+        let mut buffer = MassiveBuffer::default();
+        buffer.set_max_size(2 * MAX_BODY_SIZE);
+        buffer.add(fragments[0].clone()).unwrap();
+        buffer.add(fragments[1].clone()).unwrap();
+        buffer.add(fragments[2].clone()).unwrap();
+        assert!(matches!(
+            buffer.add(fragments[3].clone()),
+            Err(MassiveError::TooLarge)
+        ));
+        assert!(matches!(
+            buffer.add(fragments[3].clone()),
+            Err(MassiveError::HeaderMissing)
+        ));
+
+        buffer.set_max_size(10_000);
+        for fragment in fragments {
+            buffer.add(fragment).unwrap();
+        }
+        assert_eq!(buffer.collect(), Some(message));
+    }
+
+    fn fragment(data: &'static [u8]) -> Message {
+        Message::new(Req, Framework, MASSIVE_OPERATION, Bytes::from_static(data))
+    }
+
+    #[test]
+    fn malformed_fragments_reset_the_buffer() {
+        let mut buffer = MassiveBuffer::default();
+        assert!(matches!(
+            buffer.add(fragment(&[])),
+            Err(MassiveError::Malformed)
+        ));
+        assert!(matches!(
+            buffer.add(fragment(&[1, 1])),
+            Err(MassiveError::Malformed)
+        ));
+
+        let fragments = split(Message::new(Ack, Game, 1, Bytes::from(vec![7u8; 10_000]))).unwrap();
+        buffer.add(fragments[0].clone()).unwrap();
+        buffer.add(fragments[1].clone()).unwrap();
+
+        assert!(matches!(
+            buffer.add(fragment(&[1])),
+            Err(MassiveError::Malformed)
+        ));
+        assert!(matches!(
+            buffer.add(fragments[2].clone()),
+            Err(MassiveError::HeaderMissing)
+        ));
+    }
+
+    #[test]
+    fn new_header_discards_incomplete_massive() {
+        let message = Message::new(Ack, Game, 1, Bytes::from(vec![7u8; 10_000]));
+        let fragments = split(message.clone()).unwrap();
+
+        let mut buffer = MassiveBuffer::default();
+        buffer.add(fragments[0].clone()).unwrap();
+        buffer.add(fragments[1].clone()).unwrap();
+
+        for fragment in fragments {
+            buffer.add(fragment).unwrap();
+        }
+        assert_eq!(buffer.collect(), Some(message));
+    }
+}
@@ -12,3 +12,6 @@ mod id;
 
 pub use self::message::{Message, MAX_MESSAGE_SIZE};
 mod message;
+
+pub use self::parse_error::MessageParseError;
+mod parse_error;
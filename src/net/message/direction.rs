@@ -22,19 +22,19 @@ pub enum MessageDirection {
     ///
     /// The message is sent as answer to a previous message
     Ack,
+
+    /// Unknown direction bits, kept as they are so the message can still be forwarded
+    Unknown(u16),
 }
 
 impl Display for MessageDirection {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{}",
-            match self {
-                MessageDirection::NoDir => "NoDir",
-                MessageDirection::Req => "Req",
-                MessageDirection::Ack => "Ack",
-            }
-        )
+        match self {
+            MessageDirection::NoDir => write!(f, "NoDir"),
+            MessageDirection::Req => write!(f, "Req"),
+            MessageDirection::Ack => write!(f, "Ack"),
+            MessageDirection::Unknown(bits) => write!(f, "Unknown({})", bits),
+        }
     }
 }
 
@@ -44,6 +44,7 @@ impl MessageDirection {
             MessageDirection::NoDir => NO_DIR,
             MessageDirection::Req => REQ,
             MessageDirection::Ack => ACK,
+            MessageDirection::Unknown(bits) => bits,
         }
     }
 
@@ -52,7 +53,7 @@ impl MessageDirection {
             NO_DIR => MessageDirection::NoDir,
             REQ => MessageDirection::Req,
             ACK => MessageDirection::Ack,
-            _ => panic!("invalid message direction"),
+            bits => MessageDirection::Unknown(bits),
         }
     }
 }
@@ -1,10 +1,15 @@
 use crate::net::message::id::MessageId;
-use bytes::{Buf, BufMut, Bytes, BytesMut};
+use crate::net::message::MessageParseError;
+use crate::security::blowfish_compat::BLOCK_SIZE;
+use bytes::{BufMut, Bytes, BytesMut};
 use std::fmt::{Display, Formatter};
 
 pub const HEADER_SIZE: usize = 6;
 const HEADER_ENC_MASK: u16 = 0x8000;
 
+/// Largest data size the size field can declare next to the encryption flag
+pub(crate) const MAX_DATA_SIZE: usize = !HEADER_ENC_MASK as usize;
+
 /// Message Header
 ///
 /// The header of a network message which is always exactly 6 bytes
@@ -45,34 +50,60 @@ impl Header {
         &self.id
     }
 
+    /// The size of the whole message on the wire
+    ///
+    /// Encrypted messages carry everything behind the size field padded to whole blowfish blocks.
     pub fn message_size(&self) -> u16 {
-        self.data_size() + HEADER_SIZE as u16
+        if self.is_encrypted() {
+            let encrypted_size =
+                (self.data_size() + HEADER_SIZE as u16 - 2).next_multiple_of(BLOCK_SIZE as u16);
+            encrypted_size + 2
+        } else {
+            self.data_size() + HEADER_SIZE as u16
+        }
     }
 
     pub fn data_size(&self) -> u16 {
         self.size & !HEADER_ENC_MASK
     }
-}
 
-impl From<Bytes> for Header {
-    fn from(mut value: Bytes) -> Self {
-        Self {
-            size: value.get_u16_le(),
-            id: MessageId::from(value.get_u16_le()),
-            sequence: value.get_u8(),
-            checksum: value.get_u8(),
+    /// Whether the encryption flag (MSB of the size field) is set
+    pub fn is_encrypted(&self) -> bool {
+        self.size & HEADER_ENC_MASK != 0
+    }
+
+    /// Sets or clears the encryption flag (MSB of the size field)
+    pub fn set_encrypted(&mut self, encrypted: bool) {
+        if encrypted {
+            self.size |= HEADER_ENC_MASK;
+        } else {
+            self.size &= !HEADER_ENC_MASK;
         }
     }
 }
 
-impl From<&[u8]> for Header {
-    fn from(buffer: &[u8]) -> Self {
-        Header {
-            size: u16::from_le_bytes(buffer[0..2].try_into().unwrap()),
-            id: MessageId::from(u16::from_le_bytes(buffer[2..4].try_into().unwrap())),
-            checksum: buffer[5],
-            sequence: buffer[4],
+impl TryFrom<Bytes> for Header {
+    type Error = MessageParseError;
+
+    fn try_from(value: Bytes) -> Result<Self, Self::Error> {
+        Header::try_from(value.as_ref())
+    }
+}
+
+impl TryFrom<&[u8]> for Header {
+    type Error = MessageParseError;
+
+    fn try_from(buffer: &[u8]) -> Result<Self, Self::Error> {
+        if buffer.len() < HEADER_SIZE {
+            return Err(MessageParseError::IncompleteHeader(buffer.len()));
         }
+
+        Ok(Header {
+            size: u16::from_le_bytes([buffer[0], buffer[1]]),
+            id: MessageId::try_from(&buffer[2..4])?,
+            sequence: buffer[4],
+            checksum: buffer[5],
+        })
     }
 }
 
@@ -88,4 +119,31 @@ impl Into<Bytes> for Header {
     }
 }
 
-// TODO: write a test with a known byte array to check if conversions are correct.
+#[cfg(test)]
+mod tests {
+    use crate::net::message::{Header, MessageId, MessageParseError};
+    use bytes::Bytes;
+
+    #[test]
+    fn conversions_with_known_bytes() {
+        let bytes = Bytes::from_static(&[0x03, 0x80, 0x01, 0x70, 0xAB, 0xCD]);
+
+        let header = Header::try_from(bytes.clone()).unwrap();
+        assert_eq!(header.data_size(), 3);
+        assert!(header.is_encrypted());
+        assert_eq!(*header.id(), MessageId::from(0x7001));
+        assert_eq!(header.sequence, 0xAB);
+        assert_eq!(header.checksum, 0xCD);
+
+        let back: Bytes = header.into();
+        assert_eq!(back, bytes);
+    }
+
+    #[test]
+    fn incomplete_header_fails() {
+        assert_eq!(
+            Header::try_from([0x03u8, 0x00, 0x01].as_slice()),
+            Err(MessageParseError::IncompleteHeader(3))
+        );
+    }
+}
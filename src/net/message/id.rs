@@ -1,5 +1,6 @@
 use crate::net::message::direction::MessageDirection;
 use crate::net::message::kind::MessageKind;
+use crate::net::message::MessageParseError;
 use bitfield_struct::bitfield;
 use std::fmt::{Display, Formatter};
 
@@ -49,10 +50,23 @@ impl Display for MessageId {
     }
 }
 
+impl TryFrom<&[u8]> for MessageId {
+    type Error = MessageParseError;
+
+    /// Reads the little endian [MessageId] from the first two bytes of `buffer`
+    fn try_from(buffer: &[u8]) -> Result<Self, Self::Error> {
+        let bytes = buffer
+            .get(0..2)
+            .ok_or(MessageParseError::IncompleteId(buffer.len()))?;
+
+        Ok(MessageId::from(u16::from_le_bytes([bytes[0], bytes[1]])))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::net::message::id::MessageId;
-    use crate::net::message::{MessageDirection, MessageKind};
+    use crate::net::message::{MessageDirection, MessageKind, MessageParseError};
 
     #[test]
     fn back_and_forth_works() {
@@ -67,4 +81,24 @@ mod tests {
             .with_operation(0);
         assert_eq!(id_from_struct.0, 0x9000);
     }
+
+    #[test]
+    fn unknown_direction_is_lossless() {
+        let id = MessageId::from(0xF001);
+        assert_eq!(id.direction(), MessageDirection::Unknown(3));
+        assert_eq!(id.kind(), MessageKind::Game);
+        assert_eq!(u16::from(id), 0xF001);
+    }
+
+    #[test]
+    fn try_from_short_slice_fails() {
+        assert_eq!(
+            MessageId::try_from([0x00u8].as_slice()),
+            Err(MessageParseError::IncompleteId(1))
+        );
+        assert_eq!(
+            MessageId::try_from([0x00u8, 0x50].as_slice()),
+            Ok(MessageId::from(0x5000))
+        );
+    }
 }
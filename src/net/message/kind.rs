@@ -20,20 +20,20 @@ pub enum MessageKind {
 
     /// Actual Gameplay (Agent interactions, Shard operations, ...)
     Game,
+
+    /// Unknown kind bits, kept as they are so the message can still be forwarded
+    Unknown(u16),
 }
 
 impl Display for MessageKind {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{}",
-            match self {
-                MessageKind::None => "None",
-                MessageKind::NetEngine => "NetEngine",
-                MessageKind::Framework => "Framework",
-                MessageKind::Game => "Game",
-            }
-        )
+        match self {
+            MessageKind::None => write!(f, "None"),
+            MessageKind::NetEngine => write!(f, "NetEngine"),
+            MessageKind::Framework => write!(f, "Framework"),
+            MessageKind::Game => write!(f, "Game"),
+            MessageKind::Unknown(bits) => write!(f, "Unknown({})", bits),
+        }
     }
 }
 
@@ -44,6 +44,7 @@ impl MessageKind {
             MessageKind::NetEngine => NET_ENGINE,
             MessageKind::Framework => FRAMEWORK,
             MessageKind::Game => GAME,
+            MessageKind::Unknown(bits) => bits,
         }
     }
 
@@ -53,7 +54,7 @@ impl MessageKind {
             NET_ENGINE => MessageKind::NetEngine,
             FRAMEWORK => MessageKind::Framework,
             GAME => MessageKind::Game,
-            _ => panic!("invalid message kind"),
+            bits => MessageKind::Unknown(bits),
         }
     }
 }
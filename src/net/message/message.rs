@@ -1,6 +1,8 @@
-use crate::net::message::header::Header;
-use crate::net::message::{MessageDirection, MessageId, MessageKind, HEADER_SIZE};
-use bytes::{Buf, BufMut, Bytes, BytesMut};
+use crate::net::message::header::{Header, MAX_DATA_SIZE};
+use crate::net::message::{
+    MessageDirection, MessageId, MessageKind, MessageParseError, HEADER_SIZE,
+};
+use bytes::{BufMut, Bytes, BytesMut};
 use std::fmt::{Display, Formatter};
 
 pub const MAX_MESSAGE_SIZE: usize = 4096;
@@ -12,6 +14,10 @@ pub struct Message {
 }
 
 impl Message {
+    /// Creates an unencrypted message carrying `data`.
+    ///
+    /// Data larger than [MAX_DATA_SIZE] is declared with that size, such a message is split into
+    /// massive fragments before it is sent.
     pub fn new(dir: MessageDirection, kind: MessageKind, op: usize, data: Bytes) -> Self {
         Self {
             header: Header::new(
@@ -19,14 +25,20 @@ impl Message {
                     .with_kind(kind)
                     .with_direction(dir)
                     .with_operation(op),
-                data.len() as u16,
+                data.len().min(MAX_DATA_SIZE) as u16,
             ),
             data,
         }
     }
 
     pub fn is_encrypted(&self) -> bool {
-        self.header.data_size() & 0x8000 != 0
+        self.header.is_encrypted()
+    }
+
+    /// Whether the message is larger than [MAX_MESSAGE_SIZE] on the wire and has to be split into
+    /// massive fragments before it is sent
+    pub fn is_oversized(&self) -> bool {
+        self.header.message_size() as usize > MAX_MESSAGE_SIZE
     }
 
     pub fn header_mut(&mut self) -> &mut Header {
@@ -58,21 +70,32 @@ impl From<(Header, Bytes)> for Message {
     }
 }
 
-impl From<Bytes> for Message {
-    fn from(mut value: Bytes) -> Self {
-        Self {
-            header: Header::from(value.copy_to_bytes(HEADER_SIZE)),
-            data: value,
+impl TryFrom<Bytes> for Message {
+    type Error = MessageParseError;
+
+    /// Parses a whole message as it is sent on the wire
+    fn try_from(mut value: Bytes) -> Result<Self, Self::Error> {
+        let header = Header::try_from(value.as_ref())?;
+
+        let declared = header.message_size() as usize - HEADER_SIZE;
+        let actual = value.len() - HEADER_SIZE;
+        if declared != actual {
+            return Err(MessageParseError::SizeMismatch { declared, actual });
         }
+
+        Ok(Self {
+            header,
+            data: value.split_off(HEADER_SIZE),
+        })
     }
 }
 
-impl From<&[u8]> for Message {
-    fn from(data: &[u8]) -> Self {
-        Message {
-            header: Header::from(&data[0..HEADER_SIZE]),
-            data: Bytes::copy_from_slice(&data[6..]),
-        }
+impl TryFrom<&[u8]> for Message {
+    type Error = MessageParseError;
+
+    /// Parses a whole message as it is sent on the wire
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        Message::try_from(Bytes::copy_from_slice(data))
     }
 }
 
@@ -0,0 +1,15 @@
+/// A [Message](crate::net::message::Message) or one of its parts could not be parsed
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum MessageParseError {
+    /// Fewer bytes than a [MessageId](crate::net::message::MessageId) needs
+    IncompleteId(usize),
+
+    /// Fewer bytes than a [Header](crate::net::message::Header) needs
+    IncompleteHeader(usize),
+
+    /// The data does not match the size declared in the header
+    SizeMismatch { declared: usize, actual: usize },
+
+    /// The message is larger than the accepted maximum
+    Oversized { size: usize, max: usize },
+}
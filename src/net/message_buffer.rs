@@ -1,6 +1,33 @@
-use crate::net::message::{Header, Message, HEADER_SIZE, MAX_MESSAGE_SIZE};
+use crate::net::message::{Header, Message, MessageParseError, HEADER_SIZE, MAX_MESSAGE_SIZE};
+use bytes::BytesMut;
 
-type NetBuffer = [u8; MAX_MESSAGE_SIZE];
+/// Splits the next complete [Message] off the front of `src`.
+///
+/// Returns `Ok(None)` and leaves `src` untouched as long as it does not hold a whole message.
+/// Messages larger than `max_message_size` are reported as [MessageParseError::Oversized].
+pub(crate) fn split_message(
+    src: &mut BytesMut,
+    max_message_size: usize,
+) -> Result<Option<Message>, MessageParseError> {
+    if src.len() < HEADER_SIZE {
+        return Ok(None);
+    }
+
+    let message_size = Header::try_from(&src[..HEADER_SIZE])?.message_size() as usize;
+    if message_size > max_message_size {
+        return Err(MessageParseError::Oversized {
+            size: message_size,
+            max: max_message_size,
+        });
+    }
+
+    if src.len() < message_size {
+        src.reserve(message_size - src.len());
+        return Ok(None);
+    }
+
+    Message::try_from(src.split_to(message_size).freeze()).map(Some)
+}
 
 /// A helper struct to manage reading complete [Message]s.
 ///
@@ -9,152 +36,123 @@ type NetBuffer = [u8; MAX_MESSAGE_SIZE];
 /// contain a complete [Message], this struct will retain the partial
 /// data until subsequent reads supply the remaining portion to
 /// complete the [Message].
+///
+/// A `MessageBuffer` is meant to live as long as the stream it reads from.
 pub struct MessageBuffer {
-    incomplete_ptr: usize,
-    incomplete_buffer: NetBuffer,
+    buffer: BytesMut,
+    max_message_size: usize,
 }
 
 impl Default for MessageBuffer {
-    /// Create a new empty `MessageBuffer`.
+    /// Create a new empty `MessageBuffer` accepting messages up to [MAX_MESSAGE_SIZE].
     fn default() -> Self {
-        Self {
-            incomplete_ptr: 0,
-            incomplete_buffer: [0; MAX_MESSAGE_SIZE],
-        }
+        MessageBuffer::new(MAX_MESSAGE_SIZE)
     }
 }
 
 impl MessageBuffer {
-    /// Handle the case where we have a partial message left in our buffer from a previous read.
-    fn handle_incomplete_buffer(&mut self, incoming_data: &NetBuffer, len: usize) {
-        let incomplete_header = Header::from(&self.incomplete_buffer[..HEADER_SIZE]);
-        let packet_size = incomplete_header.message_size() as usize;
-        let missing_data = packet_size - self.incomplete_ptr;
-        let size_to_copy = std::cmp::min(len, missing_data);
-
-        self.incomplete_buffer[self.incomplete_ptr..self.incomplete_ptr + size_to_copy]
-            .copy_from_slice(&incoming_data[..size_to_copy]);
-
-        self.incomplete_ptr += size_to_copy;
+    /// Create a new empty `MessageBuffer` accepting messages up to `max_message_size`.
+    pub fn new(max_message_size: usize) -> Self {
+        Self {
+            buffer: BytesMut::with_capacity(max_message_size),
+            max_message_size,
+        }
     }
 
     /// Read incoming data and return a vector of complete [Message]s.
-    pub fn read(&mut self, incoming_data: NetBuffer, len: usize) -> Vec<Message> {
-        let mut messages = Vec::new();
-        let mut ptr = 0;
-
-        // Check if we have a partial message left from a previous read
-        if self.incomplete_ptr > 0 {
-            self.handle_incomplete_buffer(&incoming_data, len);
-
-            if self.incomplete_ptr < HEADER_SIZE {
-                return messages;
-            }
-
-            let incomplete_header = Header::from(&self.incomplete_buffer[..HEADER_SIZE]);
-            let packet_size = incomplete_header.message_size() as usize;
-
-            if self.incomplete_ptr == packet_size {
-                let message = Message::from(&self.incomplete_buffer[..packet_size]);
-                messages.push(message);
-                self.incomplete_ptr = 0;
-            }
-
-            ptr = self.incomplete_ptr;
-        }
+    ///
+    /// Fails as soon as a message cannot be framed. The stream cannot be recovered afterwards.
+    pub fn read(&mut self, incoming_data: &[u8]) -> Result<Vec<Message>, MessageParseError> {
+        self.buffer.extend_from_slice(incoming_data);
 
-        // Process the remaining incoming data
-        while len - ptr >= HEADER_SIZE {
-            let header = Header::from(&incoming_data[ptr..ptr + HEADER_SIZE]);
-            let message_size = header.message_size() as usize;
-            let recv_len = len - ptr;
-
-            if recv_len < message_size {
-                self.incomplete_buffer
-                    .copy_from_slice(&incoming_data[ptr..]);
-                self.incomplete_ptr = recv_len;
-                break;
-            }
-
-            messages.push(Message::from(&incoming_data[ptr..ptr + message_size]));
-            ptr += message_size;
+        let mut messages = Vec::new();
+        while let Some(message) = split_message(&mut self.buffer, self.max_message_size)? {
+            messages.push(message);
         }
 
-        messages
+        Ok(messages)
     }
 }
 
-#[cfg(tests)]
+#[cfg(test)]
 mod tests {
-    use crate::net::message::{Header, Message, MessageId, HEADER_SIZE, MAX_MESSAGE_SIZE};
+    use crate::net::message::{Header, Message, MessageId, MessageParseError, HEADER_SIZE};
     use crate::net::MessageBuffer;
     use bytes::{BufMut, Bytes, BytesMut};
 
-    #[test]
-    fn read_complete_message_in_one_go() {
-        let mut buffer = MessageBuffer::default();
-
+    fn message() -> Message {
         let mut data = BytesMut::new();
         data.put_u8(0);
         data.put_u8(1);
         data.put_u8(2);
 
-        let message = Message::new(
+        Message::from((
             Header::new(MessageId::from(0x5000), data.len() as u16),
-            data.clone().freeze(),
-        );
-
-        let message_bytes: Bytes = message.into();
+            data.freeze(),
+        ))
+    }
 
-        let mut net_buffer = [0; MAX_MESSAGE_SIZE];
-        net_buffer[..message_bytes.len()].copy_from_slice(message_bytes.as_ref());
+    #[test]
+    fn read_complete_message_in_one_go() {
+        let mut buffer = MessageBuffer::default();
+        let message_bytes: Bytes = message().into();
 
-        let messages = buffer.read(net_buffer, 14);
-        assert_eq!(messages.len(), 1);
-        assert_eq!(
-            messages[0],
-            Message::new(
-                Header::new(MessageId::from(0x5000), data.len() as u16),
-                data.clone().freeze()
-            )
-        );
+        let messages = buffer.read(message_bytes.as_ref()).unwrap();
+        assert_eq!(messages, vec![message()]);
     }
 
     #[test]
     fn read_split_message_across_reads() {
         let mut buffer = MessageBuffer::default();
+        let message_bytes: Bytes = message().into();
 
-        let mut data = BytesMut::new();
-        data.put_u8(0);
-        data.put_u8(1);
-        data.put_u8(2);
+        let messages = buffer.read(&message_bytes[..HEADER_SIZE]).unwrap();
+        assert_eq!(messages.len(), 0);
 
-        let message = Message::new(
-            Header::new(MessageId::from(0x5000), data.len() as u16),
-            data.clone().freeze(),
-        );
+        let messages = buffer.read(&message_bytes[HEADER_SIZE..]).unwrap();
+        assert_eq!(messages, vec![message()]);
+    }
 
-        let message_bytes: Bytes = message.into();
-        let first_bytes = &message_bytes.as_ref()[..HEADER_SIZE];
-        let second_bytes = &message_bytes.as_ref()[HEADER_SIZE..];
+    #[test]
+    fn read_split_inside_header() {
+        let mut buffer = MessageBuffer::default();
+        let message_bytes: Bytes = message().into();
 
-        let mut first_net_buffer = [0; MAX_MESSAGE_SIZE];
-        first_net_buffer[..HEADER_SIZE].copy_from_slice(first_bytes);
+        for byte in &message_bytes[..message_bytes.len() - 1] {
+            assert!(buffer.read(&[*byte]).unwrap().is_empty());
+        }
 
-        let mut second_net_buffer = [0; MAX_MESSAGE_SIZE];
-        second_net_buffer[..data.len()].copy_from_slice(second_bytes);
+        let messages = buffer
+            .read(&message_bytes[message_bytes.len() - 1..])
+            .unwrap();
+        assert_eq!(messages, vec![message()]);
+    }
 
-        let messages = buffer.read(first_net_buffer, HEADER_SIZE);
-        assert_eq!(messages.len(), 0);
+    #[test]
+    fn read_multiple_messages_with_trailing_part() {
+        let mut buffer = MessageBuffer::default();
+        let message_bytes: Bytes = message().into();
+
+        let mut stream = BytesMut::new();
+        stream.put_slice(&message_bytes);
+        stream.put_slice(&message_bytes);
+        stream.put_slice(&message_bytes[..4]);
+
+        let messages = buffer.read(&stream).unwrap();
+        assert_eq!(messages, vec![message(), message()]);
+
+        let messages = buffer.read(&message_bytes[4..]).unwrap();
+        assert_eq!(messages, vec![message()]);
+    }
+
+    #[test]
+    fn read_oversized_message_fails() {
+        let mut buffer = MessageBuffer::new(8);
+        let message_bytes: Bytes = message().into();
 
-        let messages = buffer.read(second_net_buffer, 3);
-        assert_eq!(messages.len(), 1);
         assert_eq!(
-            messages[0],
-            Message::new(
-                Header::new(MessageId::from(0x5000), data.len() as u16),
-                data.clone().freeze()
-            )
+            buffer.read(&message_bytes),
+            Err(MessageParseError::Oversized { size: 9, max: 8 })
         );
     }
 }
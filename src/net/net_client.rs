@@ -1,14 +1,25 @@
-use crate::net::massive::MassiveBuffer;
-use crate::net::message::MessageDirection::Req;
-use crate::net::message::MessageKind::Framework;
+use crate::net::framework::NetPing;
+use crate::net::io::StringEncoding;
+use crate::net::massive::{self, MassiveBuffer};
 use crate::net::message::{Message, MessageId};
-use crate::net::NetConnection;
-use crate::security::Security;
+use crate::net::net_client_handle::HandleCommand;
+use crate::net::net_connection::CHANNEL_CAPACITY;
+use crate::net::{DisconnectReason, NetClientHandle, NetConnection, NetEvent};
+use crate::security::{DesyncPolicy, Security};
 use bytes::Buf;
-use log::{error, trace};
+use log::{error, trace, warn};
 use queues::{IsQueue, Queue};
 use std::collections::HashMap;
+use std::future::Future;
+use std::ops::ControlFlow;
+use std::pin::Pin;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
 use tokio::net::TcpStream;
+use tokio::select;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::mpsc::{channel, Receiver};
+use tokio::time::{sleep_until, Instant};
 #[macro_export]
 
 macro_rules! construct_processor_table {
@@ -47,6 +58,17 @@ macro_rules! construct_processor_table {
     };
 }
 
+/// How long a [NetClient] stays silent before it sends a [NetPing], the default for clients
+/// created with [NetClient::connect]
+pub const DEFAULT_KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Number of [NetEvent]s a [NetClient] keeps until they are taken, older ones are dropped first
+pub const MAX_QUEUED_EVENTS: usize = 256;
+
+/// Largest message a [NetClient] reassembles from massive fragments unless set otherwise with
+/// [NetClient::set_max_massive_size]
+pub const DEFAULT_MAX_MASSIVE_SIZE: usize = 1024 * 1024;
+
 pub type Processor = Box<dyn Process + Send>;
 pub type MessageTable = HashMap<MessageId, Processor>;
 
@@ -54,41 +76,100 @@ pub trait Process {
     fn process(&mut self, net_client: &mut NetClient, m: Message);
 }
 
+pub type ProcessFuture<'a> = Pin<Box<dyn Future<Output = ()> + Send + 'a>>;
+pub type AsyncProcessor = Box<dyn AsyncProcess + Send>;
+pub type AsyncMessageTable = HashMap<MessageId, AsyncProcessor>;
+
+/// The async counterpart of [Process], used by [NetClient::run]
+///
+/// Every [Process] is an [AsyncProcess] as well, so synchronous processors can be put into an
+/// [AsyncMessageTable] unchanged.
+pub trait AsyncProcess {
+    fn process<'a>(&'a mut self, net_client: &'a mut NetClient, m: Message) -> ProcessFuture<'a>;
+}
+
+impl<T: Process> AsyncProcess for T {
+    fn process<'a>(&'a mut self, net_client: &'a mut NetClient, m: Message) -> ProcessFuture<'a> {
+        Process::process(self, net_client, m);
+        Box::pin(std::future::ready(()))
+    }
+}
+
 pub struct NetClient {
     name: String,
     connection: NetConnection,
     massive_buffer: MassiveBuffer,
+    reassemble_massive: bool,
     security: Security,
+    desync_policy: DesyncPolicy,
     loopback: Queue<Message>,
+    /// messages waiting for room in the outbound channel, not yet encoded
+    outbound: Queue<Message>,
+    events: Queue<NetEvent>,
+    disconnect_reported: bool,
+    string_encoding: StringEncoding,
+    keep_alive: Option<Duration>,
+    last_outbound: Instant,
+    handle: NetClientHandle,
+    commands: Receiver<HandleCommand>,
 }
 
-impl From<TcpStream> for NetClient {
-    fn from(value: TcpStream) -> Self {
-        let connection: NetConnection = value.into();
+impl From<NetConnection> for NetClient {
+    fn from(connection: NetConnection) -> Self {
+        let name = String::from("Unidentified");
+        let (commands, command_receiver) = channel(CHANNEL_CAPACITY);
+        let handle = NetClientHandle {
+            name: Arc::new(RwLock::new(name.clone())),
+            commands,
+            state: connection.watch_state(),
+            close_request: connection.watch_close_request(),
+        };
+
         Self {
             connection,
             massive_buffer: MassiveBuffer::default(),
+            reassemble_massive: true,
             security: Security::default(),
-            name: String::from("Unidentified"),
+            desync_policy: DesyncPolicy::default(),
+            name,
             loopback: Queue::new(),
+            outbound: Queue::new(),
+            events: Queue::new(),
+            disconnect_reported: false,
+            string_encoding: StringEncoding::default(),
+            keep_alive: None,
+            last_outbound: Instant::now(),
+            handle,
+            commands: command_receiver,
         }
     }
 }
 
+impl From<TcpStream> for NetClient {
+    fn from(value: TcpStream) -> Self {
+        let connection: NetConnection = value.into();
+        connection.into()
+    }
+}
+
 impl NetClient {
+    /// Connects to `addr` as the initiating side, which keeps the connection alive with a
+    /// [NetPing] every [DEFAULT_KEEP_ALIVE_INTERVAL].
     pub async fn connect(addr: &str) -> std::io::Result<Self> {
         let connection = NetConnection::open(addr).await?;
-        Ok(Self {
-            connection,
-            massive_buffer: MassiveBuffer::default(),
-            security: Security::default(),
-            name: String::from("Unidentified"),
-            loopback: Queue::new(),
-        })
+        let mut client: NetClient = connection.into();
+        client.set_keep_alive(Some(DEFAULT_KEEP_ALIVE_INTERVAL));
+        Ok(client)
+    }
+
+    /// Creates a [NetClientHandle] that sends and closes through this client
+    pub fn handle(&self) -> NetClientHandle {
+        self.handle.clone()
     }
 
     pub fn identify(&mut self, name: &str) {
         self.name = name.to_owned();
+        *self.handle.name.write().expect("name lock poisoned") = self.name.clone();
         // self.connection.identify(name);
     }
 
@@ -96,6 +177,21 @@ impl NetClient {
         self.connection.close();
     }
 
+    /// Whether the underlying connection is still open
+    pub fn is_alive(&self) -> bool {
+        self.connection.is_alive()
+    }
+
+    /// The reason the underlying connection was closed, `None` while it is alive
+    pub fn disconnect_reason(&self) -> Option<DisconnectReason> {
+        self.connection.disconnect_reason()
+    }
+
+    /// Resolves with the [DisconnectReason] once the underlying connection is closed
+    pub fn closed(&self) -> impl std::future::Future<Output = DisconnectReason> {
+        self.connection.closed()
+    }
+
     pub fn set_security(&mut self, security: Security) {
         self.security = security;
     }
@@ -104,27 +200,118 @@ impl NetClient {
         &mut self.security
     }
 
+    /// Sets how inbound messages failing the error detection are handled
+    pub fn set_desync_policy(&mut self, policy: DesyncPolicy) {
+        self.desync_policy = policy;
+    }
+
+    /// Sets whether inbound massive fragments are reassembled before they are dispatched.
+    ///
+    /// Enabled by default. A transparent proxy disables it to forward the raw fragments.
+    pub fn set_massive_reassembly(&mut self, enabled: bool) {
+        self.reassemble_massive = enabled;
+    }
+
+    /// Sets the largest message reassembled from massive fragments, defaults to
+    /// [DEFAULT_MAX_MASSIVE_SIZE]. A massive growing larger is discarded.
+    pub fn set_max_massive_size(&mut self, max_size: usize) {
+        self.massive_buffer.set_max_size(max_size);
+    }
+
+    /// Sets how long the client may stay silent before it sends a [NetPing], `None` disables it.
+    ///
+    /// Enabled with [DEFAULT_KEEP_ALIVE_INTERVAL] for clients created with [NetClient::connect],
+    /// disabled for accepted connections. Pings are only sent while the connection is alive.
+    pub fn set_keep_alive(&mut self, interval: Option<Duration>) {
+        self.keep_alive = interval;
+    }
+
+    pub fn keep_alive(&self) -> Option<Duration> {
+        self.keep_alive
+    }
+
+    /// Sends a [NetPing] if nothing was sent for the keep-alive interval
+    fn keep_alive_if_due(&mut self) {
+        if matches!(self.keep_alive_deadline(), Some(deadline) if deadline <= Instant::now()) {
+            trace!("{} idle, sending keep-alive", self.name);
+            self.send(NetPing.into());
+        }
+    }
+
+    fn keep_alive_deadline(&self) -> Option<Instant> {
+        if !self.connection.is_alive() {
+            return None;
+        }
+
+        self.keep_alive
+            .map(|interval| self.last_outbound + interval)
+    }
+
+    /// Sets the [StringEncoding] handlers should use for the strings of this connection
+    pub fn set_string_encoding(&mut self, encoding: StringEncoding) {
+        self.string_encoding = encoding;
+    }
+
+    /// The [StringEncoding] the peer uses for strings, UTF-8 unless set otherwise
+    ///
+    /// Pass it to [BytesExtension::get_string_with](crate::net::io::BytesExtension::get_string_with)
+    /// or [from_bytes_with](crate::net::io::from_bytes_with) when reading names or chat.
+    pub fn string_encoding(&self) -> StringEncoding {
+        self.string_encoding
+    }
+
+    /// Queues a [NetEvent] to be taken by the user, dropping the oldest one if the queue is full
+    pub(crate) fn emit(&mut self, event: NetEvent) {
+        if self.events.size() >= MAX_QUEUED_EVENTS {
+            if let Ok(dropped) = self.events.remove() {
+                warn!("{} dropped unhandled event {:?}", self.name, dropped);
+            }
+        }
+        self.events.add(event).expect("never err");
+    }
+
+    /// Takes the oldest queued [NetEvent]
+    ///
+    /// At most [MAX_QUEUED_EVENTS] are kept, events nobody takes are eventually dropped.
+    pub fn take_event(&mut self) -> Option<NetEvent> {
+        self.events.remove().ok()
+    }
+
     pub fn process_messages(
         &mut self,
         message_table: &mut MessageTable,
         default_handler: &mut Processor,
         limit: usize,
     ) {
+        self.flush();
+        // commands stay queued while earlier messages still wait for room
+        while self.outbound.size() == 0 {
+            let Ok(command) = self.commands.try_recv() else {
+                break;
+            };
+            self.handle_command(command);
+        }
+        self.keep_alive_if_due();
+
         while let Ok(m) = self.loopback.remove() {
             trace!("IN  {} {}", self.name, m);
             self.process_or_default(message_table, default_handler, m);
         }
 
         let mut counter = 0;
-        while let Some(m) = self.connection.take().unwrap() {
-            trace!("IN  {} {}", self.name, m);
-
-            // decrypt
-            let m = self.security.decrypt(m);
-
-            // TODO: check error detection
+        loop {
+            let Some(m) = self.connection.take() else {
+                self.report_disconnect();
+                break;
+            };
 
-            self.process_or_default(message_table, default_handler, m);
+            match self.accept(m) {
+                ControlFlow::Break(()) => return,
+                ControlFlow::Continue(Some(m)) => {
+                    self.process_or_default(message_table, default_handler, m)
+                }
+                ControlFlow::Continue(None) => {}
+            }
 
             counter += 1;
             if counter > limit {
@@ -133,6 +320,133 @@ impl NetClient {
         }
     }
 
+    /// Dispatches every inbound [Message] to `message_table` as soon as it arrives.
+    ///
+    /// Messages without a processor are passed to `default_handler`. Finishes with the
+    /// [DisconnectReason] once the connection is closed and every received message was processed.
+    pub async fn run(
+        &mut self,
+        message_table: &mut AsyncMessageTable,
+        default_handler: &mut AsyncProcessor,
+    ) -> DisconnectReason {
+        loop {
+            while let Ok(m) = self.loopback.remove() {
+                trace!("IN  {} {}", self.name, m);
+                self.process_or_default_async(message_table, default_handler, m)
+                    .await;
+            }
+
+            let keep_alive = self.keep_alive_deadline();
+            let pending = self.outbound.size() > 0;
+            let writable = self.connection.writable();
+            let m = select! {
+                m = self.connection.recv() => m,
+                _ = writable, if pending => {
+                    self.flush();
+                    continue;
+                }
+                Some(command) = self.commands.recv(), if !pending => {
+                    self.handle_command(command);
+                    continue;
+                }
+                _ = sleep_until(keep_alive.unwrap_or_else(Instant::now)), if keep_alive.is_some() => {
+                    self.keep_alive_if_due();
+                    continue;
+                }
+            };
+
+            let Some(m) = m else {
+                break;
+            };
+
+            match self.accept(m) {
+                ControlFlow::Break(()) => break,
+                ControlFlow::Continue(Some(m)) => {
+                    self.process_or_default_async(message_table, default_handler, m)
+                        .await
+                }
+                ControlFlow::Continue(None) => {}
+            }
+        }
+
+        let reason = self.connection.closed().await;
+        self.report_disconnect();
+        reason
+    }
+
+    /// Decrypts, verifies and reassembles an inbound [Message].
+    ///
+    /// Continues with the message to dispatch, if there is one, and breaks if the connection
+    /// was closed because of it.
+    fn accept(&mut self, m: Message) -> ControlFlow<(), Option<Message>> {
+        trace!("IN  {} {}", self.name, m);
+
+        // decrypt
+        let m = match self.security.decrypt(m) {
+            Ok(m) => m,
+            Err(e) => {
+                error!("{} could not decrypt message ({:?})", self.name, e);
+                return ControlFlow::Continue(None);
+            }
+        };
+
+        if let Err(desync) = self.security.verify(&m) {
+            self.emit(NetEvent::Desync(desync));
+
+            match self.desync_policy {
+                DesyncPolicy::Log => warn!("{} desync ({:?}) in {}", self.name, desync, m),
+                DesyncPolicy::Drop => {
+                    warn!("{} desync ({:?}), dropped {}", self.name, desync, m);
+                    return ControlFlow::Continue(None);
+                }
+                DesyncPolicy::Disconnect => {
+                    error!("{} desync ({:?}), disconnecting", self.name, desync);
+                    self.connection
+                        .disconnect(DisconnectReason::SecurityViolation);
+                    return ControlFlow::Break(());
+                }
+            }
+        }
+
+        if self.reassemble_massive && massive::is_massive(m.header().id()) {
+            ControlFlow::Continue(self.collect_massive(m))
+        } else {
+            ControlFlow::Continue(Some(m))
+        }
+    }
+
+    fn handle_command(&mut self, command: HandleCommand) {
+        match command {
+            HandleCommand::Send(message) => self.send(message),
+            HandleCommand::Close => self.close(),
+        }
+    }
+
+    /// Emits [NetEvent::Disconnected] once the connection is closed and drained
+    fn report_disconnect(&mut self) {
+        if self.disconnect_reported {
+            return;
+        }
+
+        if let Some(reason) = self.connection.disconnect_reason() {
+            self.disconnect_reported = true;
+            self.emit(NetEvent::Disconnected(reason));
+        }
+    }
+
+    fn collect_massive(&mut self, m: Message) -> Option<Message> {
+        match self.massive_buffer.add(m) {
+            Ok(_) => self.massive_buffer.collect(),
+            Err(e) => {
+                error!(
+                    "{} could not add massive message to buffer! ({:?})",
+                    self.name, e
+                );
+                None
+            }
+        }
+    }
+
     fn process_or_default(
         &mut self,
         message_table: &mut MessageTable,
@@ -146,14 +460,326 @@ impl NetClient {
         }
     }
 
+    async fn process_or_default_async(
+        &mut self,
+        message_table: &mut AsyncMessageTable,
+        default_handler: &mut AsyncProcessor,
+        m: Message,
+    ) {
+        if let Some(processor) = message_table.get_mut(m.header().id()) {
+            processor.process(self, m).await;
+        } else {
+            default_handler.process(self, m).await;
+        }
+    }
+
     pub fn receive(&mut self, message: Message) {
         self.loopback.add(message).expect("never err");
     }
 
+    /// Sends a [Message], splitting it into massive fragments if it is too large for a single one.
+    /// Messages too large even for that are dropped.
+    ///
+    /// While the outbound channel is full messages are queued, they are sent in order as soon as
+    /// [NetClient::run] or [NetClient::process_messages] finds room for them.
     pub fn send(&mut self, message: Message) {
-        let message = self.security.encode(message);
+        let id = *message.header().id();
+        let fragments = match massive::split(message) {
+            Ok(fragments) => fragments,
+            Err(e) => {
+                error!("{} could not send {} ({:?})", self.name, id, e);
+                return;
+            }
+        };
+
+        self.last_outbound = Instant::now();
+        for message in fragments {
+            self.outbound.add(message).expect("never err");
+        }
+        self.flush();
+    }
+
+    /// Encodes and sends queued messages while the outbound channel has room.
+    ///
+    /// A message is only encoded once it can be sent, so the [Security] never advances for a
+    /// message that does not reach the peer.
+    fn flush(&mut self) {
+        while self.outbound.size() > 0 {
+            let permit = match self.connection.try_reserve() {
+                Ok(permit) => permit,
+                Err(TrySendError::Full(())) => return,
+                Err(TrySendError::Closed(())) => {
+                    error!(
+                        "{} could not send {} messages, connection closed",
+                        self.name,
+                        self.outbound.size()
+                    );
+                    self.outbound = Queue::new();
+                    return;
+                }
+            };
+
+            let message = self.outbound.remove().expect("queue is not empty");
+            let message = self.security.encode(message);
+
+            trace!("OUT {} {}", self.name, message);
+            permit.send(message);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::net::framework::NetPing;
+    use crate::net::message::Message;
+    use crate::net::message::MessageDirection::Req;
+    use crate::net::message::MessageKind::Game;
+    use crate::net::net_connection::CHANNEL_CAPACITY;
+    use crate::net::{
+        AsyncMessageTable, AsyncProcess, AsyncProcessor, DisconnectReason, MessageTable, NetClient,
+        NetConnection, NetEvent, Process, ProcessFuture, Processor, DEFAULT_KEEP_ALIVE_INTERVAL,
+        MAX_QUEUED_EVENTS,
+    };
+    use crate::security::blowfish_compat::{BlowfishCompat, NewBlockCipher};
+    use crate::security::SecurityBuilder;
+    use crate::test_support::{tcp_pair, KEY};
+    use bytes::Bytes;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use tokio::net::TcpListener;
+    use tokio::time::timeout;
+
+    async fn pair() -> (NetConnection, NetClient) {
+        pair_with_capacity(CHANNEL_CAPACITY).await
+    }
+
+    async fn pair_with_capacity(capacity: usize) -> (NetConnection, NetClient) {
+        let (peer, stream) = tcp_pair().await;
+        (
+            peer.into(),
+            NetConnection::with_capacity(stream, capacity).into(),
+        )
+    }
+
+    struct Ignore;
+
+    impl Process for Ignore {
+        fn process(&mut self, _: &mut NetClient, _: Message) {}
+    }
+
+    #[derive(Default, Clone)]
+    struct Recorder {
+        messages: Arc<Mutex<Vec<Message>>>,
+    }
+
+    impl AsyncProcess for Recorder {
+        fn process<'a>(&'a mut self, _: &'a mut NetClient, m: Message) -> ProcessFuture<'a> {
+            Box::pin(async move {
+                tokio::task::yield_now().await;
+                self.messages.lock().unwrap().push(m);
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn run_dispatches_until_closed() {
+        let (peer, mut client) = pair().await;
+
+        let recorder = Recorder::default();
+        let first = Message::new(Req, Game, 1, Bytes::from_static(&[1]));
+        let second = Message::new(Req, Game, 2, Bytes::from_static(&[2]));
+
+        let mut message_table = AsyncMessageTable::new();
+        message_table.insert(*first.header().id(), Box::new(recorder.clone()));
+        let mut default_handler: AsyncProcessor = Box::new(recorder.clone());
+
+        let run = tokio::spawn(async move {
+            let reason = client.run(&mut message_table, &mut default_handler).await;
+            (reason, client.take_event())
+        });
+
+        peer.send(first.clone()).await.unwrap();
+        peer.send(second.clone()).await.unwrap();
+        drop(peer);
+
+        let (reason, event) = run.await.unwrap();
+        assert_eq!(reason, DisconnectReason::PeerClosed);
+        assert_eq!(
+            event,
+            Some(NetEvent::Disconnected(DisconnectReason::PeerClosed))
+        );
+        assert_eq!(*recorder.messages.lock().unwrap(), vec![first, second]);
+    }
+
+    #[tokio::test]
+    async fn handle_sends_through_client_security() {
+        let (mut peer, mut client) = pair().await;
+        client.security_mut().blowfish = Some(BlowfishCompat::new_from_slice(&KEY).unwrap());
+        client.identify("Peer");
+
+        let handle = client.handle();
+        assert_eq!(handle.name(), "Peer");
+
+        let mut table = AsyncMessageTable::new();
+        let mut default_handler: AsyncProcessor = Box::<Recorder>::default();
+        let run = tokio::spawn(async move { client.run(&mut table, &mut default_handler).await });
+
+        let mut message = Message::new(Req, Game, 1, Bytes::from_static(&[1, 2, 3]));
+        message.header_mut().set_encrypted(true);
+        let sender = handle.clone();
+        std::thread::spawn(move || sender.blocking_send(message).unwrap())
+            .join()
+            .unwrap();
+
+        let received = peer.recv().await.unwrap();
+        assert!(received.is_encrypted());
+        assert_eq!(received.header().message_size(), 10);
+
+        handle.close().await;
+        assert_eq!(run.await.unwrap(), DisconnectReason::LocalClose);
+        assert!(handle.is_closed());
+        assert!(handle.send(received).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn handle_send_fails_once_connection_closed() {
+        let (_peer, mut client) = pair().await;
+        let handle = client.handle();
+        let message = Message::new(Req, Game, 1, Bytes::from_static(&[1]));
+
+        assert!(handle.send(message.clone()).await.is_ok());
+        client.close();
+        assert!(!handle.is_closed());
+        assert!(handle.send(message).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn handle_close_sends_queued_messages_first() {
+        let (mut peer, mut client) = pair().await;
+        let handle = client.handle();
+
+        let mut table = AsyncMessageTable::new();
+        let mut default_handler: AsyncProcessor = Box::<Recorder>::default();
+        let run = tokio::spawn(async move { client.run(&mut table, &mut default_handler).await });
+
+        let message = Message::new(Req, Game, 1, Bytes::from_static(&[1, 2, 3]));
+        handle.send(message.clone()).await.unwrap();
+        handle.close().await;
+
+        assert_eq!(peer.recv().await, Some(message));
+        assert_eq!(peer.recv().await, None);
+        assert_eq!(peer.closed().await, DisconnectReason::PeerClosed);
+        assert_eq!(run.await.unwrap(), DisconnectReason::LocalClose);
+    }
+
+    #[tokio::test]
+    async fn send_waits_for_room_in_a_full_channel() {
+        let (mut peer, mut client) = pair_with_capacity(1).await;
+        client.set_keep_alive(None);
+        client.set_security(
+            SecurityBuilder::default()
+                .encoding_requirements((false, true))
+                .error_detection((0x1234, 0x5678))
+                .build(),
+        );
+        let mut remote = SecurityBuilder::default()
+            .encoding_requirements((true, false))
+            .error_detection((0x1234, 0x5678))
+            .build();
+
+        // the connection cannot write while this loop runs, so the channel is full after one
+        for op in 0..100 {
+            client.send(Message::new(Req, Game, op, Bytes::from(vec![op as u8])));
+        }
+
+        let mut table = AsyncMessageTable::new();
+        let mut default_handler: AsyncProcessor = Box::<Recorder>::default();
+        let handle = client.handle();
+        let run = tokio::spawn(async move { client.run(&mut table, &mut default_handler).await });
+
+        for op in 0..100 {
+            let message = peer.recv().await.unwrap();
+            assert_eq!(message.header().id().operation(), op);
+            assert_eq!(remote.verify(&message), Ok(()));
+        }
+
+        handle.close().await;
+        run.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn sends_keep_alive_when_idle() {
+        let (mut peer, mut client) = pair().await;
+        client.set_keep_alive(Some(Duration::from_millis(20)));
+
+        let mut table = AsyncMessageTable::new();
+        let mut default_handler: AsyncProcessor = Box::<Recorder>::default();
+        let handle = client.handle();
+        let run = tokio::spawn(async move { client.run(&mut table, &mut default_handler).await });
+
+        let ping = timeout(Duration::from_secs(1), peer.recv()).await.unwrap();
+        assert_eq!(ping, Some(NetPing.into()));
+
+        handle.close().await;
+        run.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn keep_alive_can_be_disabled() {
+        let (mut peer, mut client) = pair().await;
+        assert_eq!(client.keep_alive(), None);
+        client.set_keep_alive(None);
+        assert_eq!(client.keep_alive(), None);
+
+        let mut table = MessageTable::new();
+        let mut default_handler: Processor = Box::new(Ignore);
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        client.process_messages(&mut table, &mut default_handler, 10);
+        assert!(timeout(Duration::from_millis(50), peer.recv())
+            .await
+            .is_err());
+
+        client.set_keep_alive(Some(Duration::from_millis(20)));
+        client.process_messages(&mut table, &mut default_handler, 10);
+        assert_eq!(peer.recv().await, Some(NetPing.into()));
+    }
+
+    #[tokio::test]
+    async fn connected_clients_keep_alive_by_default() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+
+        let client = NetClient::connect(&addr).await.unwrap();
+        let (stream, _) = listener.accept().await.unwrap();
+        let accepted: NetClient = stream.into();
+
+        assert_eq!(client.keep_alive(), Some(DEFAULT_KEEP_ALIVE_INTERVAL));
+        assert_eq!(accepted.keep_alive(), None);
+    }
+
+    #[tokio::test]
+    async fn keep_alive_stops_once_closed() {
+        let (_peer, mut client) = pair().await;
+        client.set_keep_alive(Some(Duration::from_millis(20)));
+        assert!(client.keep_alive_deadline().is_some());
+
+        client.close();
+        assert_eq!(client.keep_alive_deadline(), None);
+    }
+
+    #[tokio::test]
+    async fn drops_oldest_events_when_full() {
+        let (_peer, mut client) = pair().await;
+        for _ in 0..MAX_QUEUED_EVENTS {
+            client.emit(NetEvent::HandshakeCompleted);
+        }
+        client.emit(NetEvent::Disconnected(DisconnectReason::PeerClosed));
 
-        trace!("OUT {} {}", self.name, message);
-        self.connection.put(message).expect("fix");
+        let events: Vec<NetEvent> = std::iter::from_fn(|| client.take_event()).collect();
+        assert_eq!(events.len(), MAX_QUEUED_EVENTS);
+        assert_eq!(
+            events.last(),
+            Some(&NetEvent::Disconnected(DisconnectReason::PeerClosed))
+        );
     }
 }
@@ -0,0 +1,87 @@
+use crate::net::message::Message;
+use crate::net::DisconnectReason;
+use std::sync::{Arc, RwLock};
+use tokio::sync::mpsc::error::SendError;
+use tokio::sync::mpsc::Sender;
+use tokio::sync::watch;
+
+/// What a [NetClientHandle] asks its [NetClient](crate::net::NetClient) to do
+#[derive(Debug)]
+pub(crate) enum HandleCommand {
+    Send(Message),
+    Close,
+}
+
+/// A cheap, cloneable handle to a [NetClient](crate::net::NetClient)
+///
+/// Messages sent through a handle are queued and encoded by the owning client, so they pass
+/// through its [Security](crate::security::Security) just like [NetClient::send](crate::net::NetClient::send).
+/// Queued commands are handled by [NetClient::run](crate::net::NetClient::run) as they arrive, or
+/// by the next call to [NetClient::process_messages](crate::net::NetClient::process_messages).
+/// The queue is bounded, senders wait while it is full.
+#[derive(Debug, Clone)]
+pub struct NetClientHandle {
+    pub(crate) name: Arc<RwLock<String>>,
+    pub(crate) commands: Sender<HandleCommand>,
+    pub(crate) state: watch::Receiver<Option<DisconnectReason>>,
+    pub(crate) close_request: watch::Receiver<Option<DisconnectReason>>,
+}
+
+impl NetClientHandle {
+    /// Queues a [Message] to be sent, waiting while the queue is full.
+    ///
+    /// Fails once the owning client closes its connection or was dropped.
+    pub async fn send(&self, message: Message) -> Result<(), SendError<Message>> {
+        if self.is_disconnected() {
+            return Err(SendError(message));
+        }
+
+        self.commands
+            .send(HandleCommand::Send(message))
+            .await
+            .map_err(into_message)
+    }
+
+    /// Blocking variant of [NetClientHandle::send] for threads outside of the tokio runtime.
+    pub fn blocking_send(&self, message: Message) -> Result<(), SendError<Message>> {
+        if self.is_disconnected() {
+            return Err(SendError(message));
+        }
+
+        self.commands
+            .blocking_send(HandleCommand::Send(message))
+            .map_err(into_message)
+    }
+
+    /// Asks the owning client to close its connection once the messages queued before are sent.
+    pub async fn close(&self) {
+        // nothing left to close when the client was dropped
+        let _ = self.commands.send(HandleCommand::Close).await;
+    }
+
+    /// Blocking variant of [NetClientHandle::close] for threads outside of the tokio runtime.
+    pub fn blocking_close(&self) {
+        let _ = self.commands.blocking_send(HandleCommand::Close);
+    }
+
+    /// The name the owning client was identified with
+    pub fn name(&self) -> String {
+        self.name.read().expect("name lock poisoned").clone()
+    }
+
+    /// Whether the owning client has been dropped
+    pub fn is_closed(&self) -> bool {
+        self.commands.is_closed()
+    }
+
+    fn is_disconnected(&self) -> bool {
+        self.state.borrow().is_some() || self.close_request.borrow().is_some()
+    }
+}
+
+fn into_message(SendError(command): SendError<HandleCommand>) -> SendError<Message> {
+    match command {
+        HandleCommand::Send(message) => SendError(message),
+        HandleCommand::Close => unreachable!("a send command was queued"),
+    }
+}
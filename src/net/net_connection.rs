@@ -1,135 +1,414 @@
 use crate::net::message::{Message, MAX_MESSAGE_SIZE};
-use crate::net::MessageBuffer;
+use crate::net::{DisconnectReason, MessageBuffer, OutboundError};
 use bytes::Bytes;
-use log::error;
-use queues::{IsQueue, Queue};
-use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use log::{info, warn};
+use std::sync::Arc;
 use std::time::Duration;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::tcp::{ReadHalf, WriteHalf};
 use tokio::net::TcpStream;
-use tokio::task::JoinHandle;
-use tokio::time::sleep;
+use tokio::sync::mpsc::error::{SendError, TrySendError};
+use tokio::sync::mpsc::{channel, Permit, Receiver, Sender};
+use tokio::sync::watch;
+use tokio::time::timeout;
 use tokio::{select, spawn};
 
-type SyncMutex<T> = Arc<Mutex<T>>;
-type MessageQueue = Queue<Message>;
-type SyncQueue = SyncMutex<MessageQueue>;
+/// Number of messages each direction buffers before senders have to wait
+pub const CHANNEL_CAPACITY: usize = 1024;
+
+/// How long a closing connection may take to write the messages queued before it was closed
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
 
 pub struct NetConnection {
     // addr: String,
-    inbound: SyncQueue,
-    outbound: SyncQueue,
+    inbound: Receiver<Message>,
+    outbound: Sender<Message>,
+
+    /// `None` while the connection is alive, set once to the reason it was closed
+    state: Arc<watch::Sender<Option<DisconnectReason>>>,
 
-    run_handle: JoinHandle<()>,
+    /// `None` until [NetConnection::disconnect] asks the connection to close
+    close_request: watch::Sender<Option<DisconnectReason>>,
 }
 
 impl From<TcpStream> for NetConnection {
     fn from(value: TcpStream) -> Self {
-        let inbound = Arc::new(Mutex::new(Queue::new()));
-        let outbound = Arc::new(Mutex::new(Queue::new()));
-
-        let run_handle = spawn(NetConnection::run(value, inbound.clone(), outbound.clone()));
-
-        Self {
-            // addr: addr.to_string(),
-            run_handle,
-            inbound,
-            outbound,
-        }
+        NetConnection::with_capacity(value, CHANNEL_CAPACITY)
     }
 }
 
 impl NetConnection {
     pub async fn open(addr: &str) -> std::io::Result<Self> {
         let stream = TcpStream::connect(addr).await?;
-        let inbound = Arc::new(Mutex::new(Queue::new()));
-        let outbound = Arc::new(Mutex::new(Queue::new()));
+        Ok(stream.into())
+    }
 
-        let run_handle = spawn(NetConnection::run(
+    /// Wraps `stream` with inbound and outbound channels holding up to `capacity` messages each.
+    pub fn with_capacity(stream: TcpStream, capacity: usize) -> Self {
+        let (inbound_sender, inbound) = channel(capacity);
+        let (outbound, outbound_receiver) = channel(capacity);
+        let (state, _) = watch::channel(None);
+        let state = Arc::new(state);
+        let (close_request, close_receiver) = watch::channel(None);
+
+        spawn(NetConnection::run(
             stream,
-            inbound.clone(),
-            outbound.clone(),
+            inbound_sender,
+            outbound_receiver,
+            close_receiver,
+            state.clone(),
         ));
 
-        Ok(Self {
+        Self {
             // addr: addr.to_string(),
-            run_handle,
+            state,
+            close_request,
             inbound,
             outbound,
-        })
+        }
     }
 
-    async fn run(mut stream: TcpStream, inbound_queue: SyncQueue, outbound_queue: SyncQueue) {
+    async fn run(
+        mut stream: TcpStream,
+        inbound: Sender<Message>,
+        outbound: Receiver<Message>,
+        close_request: watch::Receiver<Option<DisconnectReason>>,
+        state: Arc<watch::Sender<Option<DisconnectReason>>>,
+    ) {
         let (read, write) = stream.split();
 
-        let f = select! {
-            r = NetConnection::inbound_loop(read, inbound_queue) => r,
-            r = NetConnection::outbound_loop(write, outbound_queue) => r,
+        let reason = select! {
+            r = NetConnection::inbound_loop(read, inbound) => r,
+            r = NetConnection::outbound_loop(write, outbound, close_request) => r,
         };
 
-        error!("select resulted in {:?}", f);
+        NetConnection::set_reason(&state, reason);
     }
 
+    /// Closes the connection with [DisconnectReason::LocalClose].
     pub fn close(&mut self) {
-        self.run_handle.abort();
+        self.disconnect(DisconnectReason::LocalClose);
     }
 
-    pub fn take(&mut self) -> Result<Option<Message>, PoisonError<MutexGuard<MessageQueue>>> {
-        Ok(self.inbound.lock()?.remove().map_or(None, |m| Some(m)))
+    /// Closes the connection, `reason` is kept unless the connection is already closed.
+    ///
+    /// No further messages are accepted. Those queued before are still written, then the
+    /// connection is shut down and `reason` is recorded.
+    pub fn disconnect(&mut self, reason: DisconnectReason) {
+        self.close_request.send_if_modified(|current| {
+            if current.is_none() {
+                *current = Some(reason);
+                true
+            } else {
+                false
+            }
+        });
     }
 
-    pub fn put(&mut self, message: Message) -> Result<(), PoisonError<MutexGuard<MessageQueue>>> {
-        let mut queue = self.outbound.lock()?;
-        queue
-            .add(message)
-            .expect("returns always Ok(None) according to docs");
-        Ok(())
+    /// Whether the connection is still open and accepts messages.
+    ///
+    /// Messages received before the connection was closed can still be taken.
+    pub fn is_alive(&self) -> bool {
+        self.state.borrow().is_none() && !self.is_closing()
     }
 
-    /// TODO: Error Mapping
-    async fn inbound_loop<'a>(mut stream: ReadHalf<'a>, inbound: SyncQueue) -> Result<(), ()> {
-        loop {
-            // stream.readable().await.map_err(|_| ())?;
-            let mut message_buffer = MessageBuffer::default();
+    /// Whether the connection was asked to close, it may still write queued messages.
+    pub fn is_closing(&self) -> bool {
+        self.close_request.borrow().is_some()
+    }
+
+    /// The reason the connection was closed, `None` while it is alive.
+    pub fn disconnect_reason(&self) -> Option<DisconnectReason> {
+        *self.state.borrow()
+    }
+
+    /// Watches the [DisconnectReason], `None` while the connection is alive.
+    pub(crate) fn watch_state(&self) -> watch::Receiver<Option<DisconnectReason>> {
+        self.state.subscribe()
+    }
+
+    /// Watches the close request, `None` until the connection is asked to close.
+    pub(crate) fn watch_close_request(&self) -> watch::Receiver<Option<DisconnectReason>> {
+        self.close_request.subscribe()
+    }
+
+    /// Resolves with the [DisconnectReason] once the connection is closed.
+    pub fn closed(&self) -> impl std::future::Future<Output = DisconnectReason> {
+        let mut state = self.state.subscribe();
+
+        async move {
+            loop {
+                if let Some(reason) = *state.borrow_and_update() {
+                    return reason;
+                }
 
-            let mut net_buffer = [0u8; MAX_MESSAGE_SIZE];
-            let len = stream.read(&mut net_buffer).await.map_err(|_| ())?;
+                if state.changed().await.is_err() {
+                    return DisconnectReason::LocalClose;
+                }
+            }
+        }
+    }
 
-            if len == 0 {
-                return Err(());
+    fn set_reason(state: &watch::Sender<Option<DisconnectReason>>, reason: DisconnectReason) {
+        let changed = state.send_if_modified(|current| {
+            if current.is_none() {
+                *current = Some(reason);
+                true
+            } else {
+                false
             }
+        });
 
-            let messages = message_buffer.read(net_buffer, len);
-            let mut inbound_queue = inbound.lock().map_err(|_| ())?;
-            for message in messages {
-                // trace!("IN  {}", message);
-                inbound_queue.add(message).map_err(|_| ())?;
+        if changed {
+            info!("connection closed ({})", reason);
+        }
+    }
+
+    /// Waits for the next inbound [Message].
+    ///
+    /// Returns `None` once the connection is closed and every received message has been taken.
+    pub async fn recv(&mut self) -> Option<Message> {
+        self.inbound.recv().await
+    }
+
+    /// Queues a [Message] to be written, waiting while the outbound channel is full.
+    ///
+    /// Messages are written as they are, oversized ones are rejected.
+    pub async fn send(&self, message: Message) -> Result<(), OutboundError> {
+        if message.is_oversized() {
+            return Err(OutboundError::Oversized(message));
+        }
+        if self.is_closing() {
+            return Err(OutboundError::Closed(message));
+        }
+
+        self.outbound
+            .send(message)
+            .await
+            .map_err(|SendError(message)| OutboundError::Closed(message))
+    }
+
+    /// Takes the next inbound [Message] if one has already been received.
+    pub fn take(&mut self) -> Option<Message> {
+        self.inbound.try_recv().ok()
+    }
+
+    /// Queues a [Message] to be written without waiting, fails if the outbound channel is full.
+    ///
+    /// Messages are written as they are, oversized ones are rejected.
+    pub fn put(&self, message: Message) -> Result<(), OutboundError> {
+        if message.is_oversized() {
+            return Err(OutboundError::Oversized(message));
+        }
+        if self.is_closing() {
+            return Err(OutboundError::Closed(message));
+        }
+
+        self.outbound.try_send(message).map_err(|e| match e {
+            TrySendError::Full(message) => OutboundError::Full(message),
+            TrySendError::Closed(message) => OutboundError::Closed(message),
+        })
+    }
+
+    /// Reserves room for one outbound [Message] without waiting.
+    ///
+    /// Fails if the outbound channel is full or the connection is closed.
+    pub fn try_reserve(&self) -> Result<Permit<'_, Message>, TrySendError<()>> {
+        if self.is_closing() {
+            return Err(TrySendError::Closed(()));
+        }
+
+        self.outbound.try_reserve()
+    }
+
+    /// Resolves once the outbound channel has room for another [Message] or is closed.
+    pub fn writable(&self) -> impl std::future::Future<Output = ()> {
+        let outbound = self.outbound.clone();
+        let closing = self.is_closing();
+
+        async move {
+            // the permit is released right away, it only signals that there is room
+            if !closing {
+                let _ = outbound.reserve().await;
             }
         }
     }
 
-    async fn outbound_loop<'a>(mut stream: WriteHalf<'a>, outbound: SyncQueue) -> Result<(), ()> {
+    async fn inbound_loop(mut stream: ReadHalf<'_>, inbound: Sender<Message>) -> DisconnectReason {
+        let mut message_buffer = MessageBuffer::default();
+        let mut net_buffer = [0u8; MAX_MESSAGE_SIZE];
+
         loop {
-            let messages = {
-                let mut outbound_queue = outbound.lock().expect("fix");
+            let len = match stream.read(&mut net_buffer).await {
+                Ok(0) => return DisconnectReason::PeerClosed,
+                Ok(len) => len,
+                Err(e) => return DisconnectReason::Io(e.kind()),
+            };
+
+            let messages = match message_buffer.read(&net_buffer[..len]) {
+                Ok(messages) => messages,
+                Err(e) => return DisconnectReason::Framing(e),
+            };
 
-                // todo: set max outbound count
-                let mut messages = Vec::<Message>::new();
-                while let Ok(message) = outbound_queue.remove() {
-                    messages.push(message);
+            for message in messages {
+                // trace!("IN  {}", message);
+                if inbound.send(message).await.is_err() {
+                    // the receiving NetConnection was dropped
+                    return DisconnectReason::LocalClose;
                 }
+            }
+        }
+    }
+
+    async fn outbound_loop(
+        mut stream: WriteHalf<'_>,
+        mut outbound: Receiver<Message>,
+        mut close_request: watch::Receiver<Option<DisconnectReason>>,
+    ) -> DisconnectReason {
+        loop {
+            let message = select! {
+                message = outbound.recv() => message,
+                // also taken when the NetConnection was dropped
+                _ = close_request.changed() => break,
+            };
 
-                messages
+            let Some(message) = message else {
+                // the sending NetConnection was dropped
+                return DisconnectReason::LocalClose;
             };
 
-            for message in messages {
-                // trace!("OUT {}", message);
-                let b: Bytes = message.into();
-                stream.write(b.as_ref()).await.map_err(|_| ())?;
+            if let Err(e) = NetConnection::write(&mut stream, message).await {
+                return DisconnectReason::Io(e.kind());
+            }
+        }
+
+        let reason = close_request
+            .borrow()
+            .unwrap_or(DisconnectReason::LocalClose);
+
+        // write what was queued before the close request, then let the peer see the close
+        outbound.close();
+        let flush = async {
+            while let Some(message) = outbound.recv().await {
+                NetConnection::write(&mut stream, message).await?;
             }
+            stream.shutdown().await
+        };
+
+        match timeout(CLOSE_TIMEOUT, flush).await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => warn!("could not flush closing connection ({})", e),
+            Err(_) => warn!("closing connection not flushed within {:?}", CLOSE_TIMEOUT),
+        }
+
+        reason
+    }
+
+    async fn write(stream: &mut WriteHalf<'_>, message: Message) -> std::io::Result<()> {
+        // trace!("OUT {}", message);
+        let b: Bytes = message.into();
+        stream.write_all(b.as_ref()).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::net::message::MessageDirection::Req;
+    use crate::net::message::MessageKind::Game;
+    use crate::net::message::{Message, MAX_MESSAGE_SIZE};
+    use crate::net::{DisconnectReason, NetConnection, OutboundError};
+    use crate::test_support::tcp_pair;
+    use bytes::Bytes;
+
+    async fn pair() -> (NetConnection, NetConnection) {
+        let (client, server) = tcp_pair().await;
+        (client.into(), server.into())
+    }
+
+    #[tokio::test]
+    async fn messages_arrive_without_polling() {
+        let (client, mut server) = pair().await;
+
+        let message = Message::new(Req, Game, 1, Bytes::from_static(&[1, 2, 3]));
+        client.send(message.clone()).await.unwrap();
+        client.put(message.clone()).unwrap();
+
+        assert_eq!(server.recv().await, Some(message.clone()));
+        assert_eq!(server.recv().await, Some(message));
+        assert_eq!(server.take(), None);
+    }
+
+    #[tokio::test]
+    async fn rejects_oversized_messages() {
+        let (client, _server) = pair().await;
+
+        let message = Message::new(Req, Game, 1, Bytes::from(vec![0; MAX_MESSAGE_SIZE]));
+        assert_eq!(
+            client.put(message.clone()),
+            Err(OutboundError::Oversized(message.clone()))
+        );
+        assert_eq!(
+            client.send(message.clone()).await,
+            Err(OutboundError::Oversized(message))
+        );
+    }
+
+    #[tokio::test]
+    async fn reports_disconnect_reasons() {
+        let (mut client, mut server) = pair().await;
+        assert!(client.is_alive());
+        assert_eq!(client.disconnect_reason(), None);
 
-            sleep(Duration::from_millis(10)).await;
+        client.close();
+        assert!(!client.is_alive());
+        assert_eq!(client.closed().await, DisconnectReason::LocalClose);
+
+        assert_eq!(server.closed().await, DisconnectReason::PeerClosed);
+        assert_eq!(server.recv().await, None);
+
+        // the first reason is kept
+        server.close();
+        assert_eq!(
+            server.disconnect_reason(),
+            Some(DisconnectReason::PeerClosed)
+        );
+    }
+
+    #[tokio::test]
+    async fn close_writes_queued_messages_first() {
+        let (mut client, mut server) = pair().await;
+
+        let message = Message::new(Req, Game, 1, Bytes::from_static(&[1, 2, 3]));
+        for _ in 0..100 {
+            client.put(message.clone()).unwrap();
         }
+        client.disconnect(DisconnectReason::SecurityViolation);
+        assert!(!client.is_alive());
+        assert!(matches!(
+            client.put(message.clone()),
+            Err(OutboundError::Closed(_))
+        ));
+
+        for _ in 0..100 {
+            assert_eq!(server.recv().await, Some(message.clone()));
+        }
+        assert_eq!(server.closed().await, DisconnectReason::PeerClosed);
+        assert_eq!(client.closed().await, DisconnectReason::SecurityViolation);
+    }
+
+    #[tokio::test]
+    async fn reports_framing_errors() {
+        let (mut peer, stream) = tcp_pair().await;
+        let server: NetConnection = stream.into();
+
+        // declares a message larger than MAX_MESSAGE_SIZE
+        tokio::io::AsyncWriteExt::write_all(&mut peer, &[0xFF, 0x7F, 0, 0, 0, 0])
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            server.closed().await,
+            DisconnectReason::Framing(_)
+        ));
     }
 }
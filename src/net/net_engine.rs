@@ -1,3 +1,16 @@
-pub use self::handshake::{ErrorDetectionSeed, ExchangeResponse, ExchangeSetup, HandshakeOptions};
-
+pub use self::handshake::{
+    ErrorDetectionSeed, ExchangeResponse, ExchangeSetup, HandshakeError, HandshakeOptions,
+};
 mod handshake;
+
+pub use self::handshake_message::{HandshakeMessage, HandshakeSetup};
+mod handshake_message;
+
+pub use self::handshake_initiator::HandshakeInitiator;
+mod handshake_initiator;
+
+pub use self::handshake_responder::HandshakeResponder;
+mod handshake_responder;
+
+use self::handshake_rng::HandshakeRng;
+mod handshake_rng;
@@ -13,6 +13,28 @@ pub struct HandshakeOptions {
     _padding: u8,
 }
 
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum HandshakeError {
+    /// The signature sent by the remote does not match the locally calculated one
+    ChallengeMismatch,
+
+    /// A handshake message arrived that does not fit the current handshake state
+    UnexpectedMessage,
+
+    /// The message is not a NetEngine handshake message
+    NotHandshake,
+
+    /// The options byte has bits set that are unknown or cannot be combined
+    InvalidOptions(u8),
+
+    /// The payload is shorter than its options require
+    Truncated { expected: usize, actual: usize },
+
+    /// The payload has bytes left after all parts required by its options
+    TrailingBytes(usize),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub struct ErrorDetectionSeed {
     pub sequence: u32,
     pub checksum: u32,
@@ -37,11 +59,14 @@ impl Into<Bytes> for ErrorDetectionSeed {
 }
 
 impl ErrorDetectionSeed {
+    pub const SIZE: usize = 8;
+
     pub fn new(sequence: u32, checksum: u32) -> Self {
         Self { sequence, checksum }
     }
 }
 
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub struct ExchangeSetup {
     pub initial_key: Key,
     pub generator: u32,
@@ -75,6 +100,8 @@ impl Into<Bytes> for ExchangeSetup {
 }
 
 impl ExchangeSetup {
+    pub const SIZE: usize = 20;
+
     pub fn new(initial_key: Key, generator: u32, prime: u32, public: u32) -> Self {
         Self {
             initial_key,
@@ -85,7 +112,7 @@ impl ExchangeSetup {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub struct ExchangeResponse {
     pub public: u32,
     pub signature: Signature,
@@ -111,6 +138,8 @@ impl From<Bytes> for ExchangeResponse {
 }
 
 impl ExchangeResponse {
+    pub const SIZE: usize = 12;
+
     pub fn new(public: u32, signature: Signature) -> Self {
         Self { public, signature }
     }
@@ -0,0 +1,175 @@
+use crate::net::message::Message;
+use crate::net::net_engine::{
+    ErrorDetectionSeed, ExchangeResponse, ExchangeSetup, HandshakeError, HandshakeMessage,
+    HandshakeRng, HandshakeSetup,
+};
+use crate::net::{NetClient, NetEvent, Process};
+use crate::security::blowfish_compat::{BlowfishCompat, NewBlockCipher};
+use crate::security::{Challenge, Exchange, Initiator, NotSet, SecurityBuilder};
+use log::{error, info};
+use rand::{Rng, RngCore};
+use std::sync::{Arc, Mutex};
+
+enum InitiatorState {
+    Idle(Exchange<NotSet>),
+    AwaitingResponse(Exchange<NotSet>),
+    AwaitingAck,
+    Completed,
+    Failed,
+}
+
+/// Drives the NetEngine handshake from the side that sends the setup (usually the server).
+///
+/// The initiator handles both the exchange response (`NetEngine, 0, Req`) and the final
+/// acknowledgement (`NetEngine, 0, Ack`). Clones share their state, so a clone has to be
+/// registered for both message ids:
+///
+/// ```ignore
+/// let initiator = HandshakeInitiator::new(exchange);
+/// let mut table = construct_processor_table! {
+///     NetEngine, 0, Req = HandshakeInitiator = initiator.clone(),
+///     NetEngine, 0, Ack = HandshakeInitiator = initiator.clone(),
+/// };
+/// initiator.start(&mut net_client);
+/// ```
+///
+/// The error detection seeds are drawn from the thread RNG, or from the RNG given to
+/// [HandshakeInitiator::with_rng].
+#[derive(Clone)]
+pub struct HandshakeInitiator {
+    state: Arc<Mutex<InitiatorState>>,
+    rng: Arc<Mutex<HandshakeRng>>,
+}
+
+impl HandshakeInitiator {
+    pub fn new(exchange: Exchange<NotSet>) -> Self {
+        Self::with_rng_source(exchange, HandshakeRng::default())
+    }
+
+    /// Draws the error detection seeds from `rng`. Together with [Exchange::generate] the
+    /// handshake becomes reproducible.
+    pub fn with_rng(exchange: Exchange<NotSet>, rng: impl RngCore + Send + 'static) -> Self {
+        Self::with_rng_source(exchange, HandshakeRng::new(rng))
+    }
+
+    fn with_rng_source(exchange: Exchange<NotSet>, rng: HandshakeRng) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(InitiatorState::Idle(exchange))),
+            rng: Arc::new(Mutex::new(rng)),
+        }
+    }
+
+    /// Installs error detection for inbound messages and sends the handshake setup.
+    pub fn start(&self, net_client: &mut NetClient) {
+        let mut state = self.state.lock().expect("handshake state poisoned");
+        let exchange = match *state {
+            InitiatorState::Idle(exchange) => exchange,
+            _ => {
+                error!("[Handshake 🤝] has already been started");
+                return;
+            }
+        };
+
+        let mut rng = self.rng.lock().expect("handshake rng poisoned");
+        let (sequence_seed, checksum_seed): (u32, u32) = (rng.gen(), rng.gen());
+        let security = SecurityBuilder::default()
+            .encoding_requirements((true, false))
+            .error_detection((sequence_seed, checksum_seed))
+            .build();
+        net_client.set_security(security);
+
+        let setup = HandshakeSetup {
+            error_detection: Some(ErrorDetectionSeed::new(sequence_seed, checksum_seed)),
+            exchange: Some(ExchangeSetup::new(
+                exchange.get_initial(),
+                exchange.get_generator(),
+                exchange.get_prime(),
+                exchange.get_local(),
+            )),
+            ..Default::default()
+        };
+
+        info!("[Handshake 🤝] 🙋🏽‍♂️ Setting Up");
+        net_client.send(HandshakeMessage::Setup(setup).into());
+        *state = InitiatorState::AwaitingResponse(exchange);
+    }
+
+    fn handle_response(
+        &self,
+        exchange: Exchange<NotSet>,
+        net_client: &mut NetClient,
+        response: ExchangeResponse,
+    ) -> Result<InitiatorState, HandshakeError> {
+        let exchange = exchange.remote(response.public);
+
+        <Initiator as Challenge>::verify(&exchange, response.signature)
+            .map_err(|_| HandshakeError::ChallengeMismatch)?;
+
+        let challenge = <Initiator as Challenge>::create(&exchange);
+
+        info!("[Handshake 🤝] 🙋🏽‍♂️ Sending Challenge");
+        net_client.send(HandshakeMessage::Challenge(challenge).into());
+
+        let key = <Initiator as Challenge>::finalize(&exchange);
+        let blowfish = BlowfishCompat::new_from_slice(key.as_slice())
+            .expect("after exchange key should be valid");
+        net_client.security_mut().blowfish = Some(blowfish);
+
+        Ok(InitiatorState::AwaitingAck)
+    }
+}
+
+impl Process for HandshakeInitiator {
+    fn process(&mut self, net_client: &mut NetClient, m: Message) {
+        let mut state = self.state.lock().expect("handshake state poisoned");
+
+        let next = HandshakeMessage::try_from(m).and_then(|message| match (&*state, message) {
+            (InitiatorState::AwaitingResponse(exchange), HandshakeMessage::Response(response)) => {
+                self.handle_response(*exchange, net_client, response)
+            }
+            (InitiatorState::AwaitingAck, HandshakeMessage::Ack) => {
+                info!("[Handshake 🤝] completed with key exchange ✅!");
+                net_client.emit(NetEvent::HandshakeCompleted);
+                Ok(InitiatorState::Completed)
+            }
+            _ => Err(HandshakeError::UnexpectedMessage),
+        });
+
+        *state = next.unwrap_or_else(|e| {
+            error!("[Handshake 🤝] failed ({:?})", e);
+            net_client.emit(NetEvent::HandshakeFailed(e));
+            net_client.close();
+            InitiatorState::Failed
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::net::net_engine::{HandshakeInitiator, HandshakeMessage};
+    use crate::net::{NetClient, NetConnection};
+    use crate::security::Exchange;
+    use crate::test_support::tcp_pair;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    async fn setup_with_seed(seed: u64) -> HandshakeMessage {
+        let (peer, stream) = tcp_pair().await;
+        let mut peer = NetConnection::from(peer);
+        let mut client = NetClient::from(stream);
+
+        let mut rng = StdRng::seed_from_u64(seed);
+        let exchange = Exchange::generate(&mut rng);
+        HandshakeInitiator::with_rng(exchange, rng).start(&mut client);
+
+        HandshakeMessage::try_from(peer.recv().await.unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn seeded_setup_is_reproducible() {
+        let setup = setup_with_seed(42).await;
+        assert!(matches!(setup, HandshakeMessage::Setup(_)));
+        assert_eq!(setup, setup_with_seed(42).await);
+        assert_ne!(setup, setup_with_seed(43).await);
+    }
+}
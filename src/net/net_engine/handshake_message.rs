@@ -0,0 +1,252 @@
+use crate::net::message::Message;
+use crate::net::message::MessageDirection::{Ack, NoDir, Req, Unknown};
+use crate::net::message::MessageKind::NetEngine;
+use crate::net::net_engine::{
+    ErrorDetectionSeed, ExchangeResponse, ExchangeSetup, HandshakeError, HandshakeOptions,
+};
+use crate::security::{Key, Signature};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+/// Bits of the [HandshakeOptions] that are part of the protocol
+const KNOWN_OPTIONS: u8 = 0x1F;
+
+/// The setup sent by the initiator of a handshake
+///
+/// Every part that is present sets its bit in the [HandshakeOptions] and follows the options
+/// byte in the order of the fields.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct HandshakeSetup {
+    pub disabled: bool,
+    pub blowfish: Option<Key>,
+    pub error_detection: Option<ErrorDetectionSeed>,
+    pub exchange: Option<ExchangeSetup>,
+}
+
+impl HandshakeSetup {
+    pub fn options(&self) -> HandshakeOptions {
+        HandshakeOptions::new()
+            .with_disabled(self.disabled)
+            .with_encryption(self.blowfish.is_some())
+            .with_error_detection(self.error_detection.is_some())
+            .with_exchange(self.exchange.is_some())
+    }
+}
+
+/// Any message of the NetEngine handshake (`0x5000` and `0x9000`)
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum HandshakeMessage {
+    /// Initiator → Responder (`0x5000`)
+    Setup(HandshakeSetup),
+
+    /// Responder → Initiator (`0x5000`)
+    ///
+    /// The only handshake payload without an options byte, it is recognized by its fixed size.
+    Response(ExchangeResponse),
+
+    /// Initiator → Responder (`0x5000`)
+    Challenge(Signature),
+
+    /// Responder → Initiator (`0x9000`)
+    Ack,
+}
+
+impl TryFrom<Message> for HandshakeMessage {
+    type Error = HandshakeError;
+
+    fn try_from(m: Message) -> Result<Self, Self::Error> {
+        let id = *m.header().id();
+        if id.kind() != NetEngine || id.operation() != 0 {
+            return Err(HandshakeError::NotHandshake);
+        }
+
+        let direction = id.direction();
+        let mut reader = m.reader();
+
+        let message = match direction {
+            Ack => HandshakeMessage::Ack,
+            Req if reader.len() == ExchangeResponse::SIZE => HandshakeMessage::Response(
+                ExchangeResponse::from(reader.split_to(ExchangeResponse::SIZE)),
+            ),
+            Req => HandshakeMessage::from_options(&mut reader)?,
+            NoDir | Unknown(_) => return Err(HandshakeError::NotHandshake),
+        };
+
+        if reader.has_remaining() {
+            Err(HandshakeError::TrailingBytes(reader.remaining()))
+        } else {
+            Ok(message)
+        }
+    }
+}
+
+impl From<HandshakeMessage> for Message {
+    fn from(value: HandshakeMessage) -> Self {
+        let mut data = BytesMut::new();
+
+        let direction = match value {
+            HandshakeMessage::Setup(setup) => {
+                data.put_u8(setup.options().into());
+                if let Some(key) = setup.blowfish {
+                    data.put_slice(key.as_slice());
+                }
+                if let Some(error_detection) = setup.error_detection {
+                    data.put::<Bytes>(error_detection.into());
+                }
+                if let Some(exchange) = setup.exchange {
+                    data.put::<Bytes>(exchange.into());
+                }
+                Req
+            }
+            HandshakeMessage::Response(response) => {
+                data.put::<Bytes>(response.into());
+                Req
+            }
+            HandshakeMessage::Challenge(signature) => {
+                data.put_u8(HandshakeOptions::new().with_challenge(true).into());
+                data.put_slice(signature.as_slice());
+                Req
+            }
+            HandshakeMessage::Ack => Ack,
+        };
+
+        Message::new(direction, NetEngine, 0, data.freeze())
+    }
+}
+
+impl HandshakeMessage {
+    fn from_options(reader: &mut Bytes) -> Result<Self, HandshakeError> {
+        let raw = take(reader, 1)?.get_u8();
+        let options = HandshakeOptions::from(raw);
+
+        if raw & !KNOWN_OPTIONS != 0 {
+            return Err(HandshakeError::InvalidOptions(raw));
+        }
+
+        if options.challenge() {
+            if raw != u8::from(HandshakeOptions::new().with_challenge(true)) {
+                return Err(HandshakeError::InvalidOptions(raw));
+            }
+
+            let mut signature = Signature::default();
+            take(reader, signature.len())?.copy_to_slice(&mut signature);
+            return Ok(HandshakeMessage::Challenge(signature));
+        }
+
+        let mut setup = HandshakeSetup {
+            disabled: options.disabled(),
+            ..Default::default()
+        };
+
+        if options.encryption() {
+            let mut key = Key::default();
+            take(reader, key.len())?.copy_to_slice(&mut key);
+            setup.blowfish = Some(key);
+        }
+
+        if options.error_detection() {
+            setup.error_detection = Some(ErrorDetectionSeed::from(take(
+                reader,
+                ErrorDetectionSeed::SIZE,
+            )?));
+        }
+
+        if options.exchange() {
+            setup.exchange = Some(ExchangeSetup::from(take(reader, ExchangeSetup::SIZE)?));
+        }
+
+        Ok(HandshakeMessage::Setup(setup))
+    }
+}
+
+fn take(reader: &mut Bytes, len: usize) -> Result<Bytes, HandshakeError> {
+    if reader.remaining() < len {
+        Err(HandshakeError::Truncated {
+            expected: len,
+            actual: reader.remaining(),
+        })
+    } else {
+        Ok(reader.split_to(len))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::net::message::Message;
+    use crate::net::message::MessageDirection::Req;
+    use crate::net::message::MessageKind::NetEngine;
+    use crate::net::net_engine::{
+        ErrorDetectionSeed, ExchangeResponse, ExchangeSetup, HandshakeError, HandshakeMessage,
+        HandshakeSetup,
+    };
+    use bytes::Bytes;
+
+    fn roundtrip(payload: &'static [u8]) -> HandshakeMessage {
+        let message = Message::new(Req, NetEngine, 0, Bytes::from_static(payload));
+        let handshake = HandshakeMessage::try_from(message.clone()).unwrap();
+        assert_eq!(Message::from(handshake), message);
+        handshake
+    }
+
+    #[test]
+    fn parses_full_setup() {
+        let payload = &[
+            0x0E, // options
+            0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, // blowfish
+            0x11, 0x00, 0x00, 0x00, 0x22, 0x00, 0x00, 0x00, // error detection
+            0x0A, 0x0B, 0x0C, 0x0D, 0x0E, 0x0F, 0x10, 0x11, // initial key
+            0x03, 0x00, 0x00, 0x00, 0x05, 0x00, 0x00, 0x00, 0x07, 0x00, 0x00, 0x00, // g, p, A
+        ];
+
+        assert_eq!(
+            roundtrip(payload),
+            HandshakeMessage::Setup(HandshakeSetup {
+                disabled: false,
+                blowfish: Some([1, 2, 3, 4, 5, 6, 7, 8]),
+                error_detection: Some(ErrorDetectionSeed::new(0x11, 0x22)),
+                exchange: Some(ExchangeSetup::new(
+                    [0x0A, 0x0B, 0x0C, 0x0D, 0x0E, 0x0F, 0x10, 0x11],
+                    3,
+                    5,
+                    7
+                )),
+            })
+        );
+    }
+
+    #[test]
+    fn parses_response_and_challenge() {
+        assert_eq!(
+            roundtrip(&[0x07, 0x00, 0x00, 0x00, 1, 2, 3, 4, 5, 6, 7, 8]),
+            HandshakeMessage::Response(ExchangeResponse::new(7, [1, 2, 3, 4, 5, 6, 7, 8]))
+        );
+
+        assert_eq!(
+            roundtrip(&[0x10, 1, 2, 3, 4, 5, 6, 7, 8]),
+            HandshakeMessage::Challenge([1, 2, 3, 4, 5, 6, 7, 8])
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_payloads() {
+        let parse = |payload: &'static [u8]| {
+            HandshakeMessage::try_from(Message::new(Req, NetEngine, 0, Bytes::from_static(payload)))
+        };
+
+        assert_eq!(
+            parse(&[0x08, 1, 2, 3]),
+            Err(HandshakeError::Truncated {
+                expected: 20,
+                actual: 3
+            })
+        );
+        assert_eq!(parse(&[0x01, 0xFF]), Err(HandshakeError::TrailingBytes(1)));
+        assert_eq!(parse(&[0x21]), Err(HandshakeError::InvalidOptions(0x21)));
+        assert_eq!(
+            parse(&[]),
+            Err(HandshakeError::Truncated {
+                expected: 1,
+                actual: 0
+            })
+        );
+    }
+}
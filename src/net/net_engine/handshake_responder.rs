@@ -0,0 +1,116 @@
+use crate::net::message::Message;
+use crate::net::net_engine::{
+    ExchangeResponse, HandshakeError, HandshakeMessage, HandshakeRng, HandshakeSetup,
+};
+use crate::net::{NetClient, NetEvent, Process};
+use crate::security::blowfish_compat::{BlowfishCompat, NewBlockCipher};
+use crate::security::{Challenge, Exchange, Responder, SecurityBuilder, Set, Signature};
+use log::{error, info};
+use rand::{Rng, RngCore};
+
+/// Drives the NetEngine handshake from the side that receives the setup (usually the client).
+///
+/// Has to be registered for `NetEngine, 0, Req`, which carries both the setup and the challenge.
+/// The private exponent is drawn from the thread RNG, or from the RNG given to
+/// [HandshakeResponder::with_rng].
+#[derive(Default)]
+pub struct HandshakeResponder {
+    exchange: Option<Exchange<Set>>,
+    rng: HandshakeRng,
+}
+
+impl Process for HandshakeResponder {
+    fn process(&mut self, net_client: &mut NetClient, m: Message) {
+        let result = HandshakeMessage::try_from(m).and_then(|message| match message {
+            HandshakeMessage::Setup(setup) => {
+                self.handle_setup(setup, net_client);
+                Ok(())
+            }
+            HandshakeMessage::Challenge(signature) => self.handle_challenge(signature, net_client),
+            _ => Err(HandshakeError::UnexpectedMessage),
+        });
+
+        if let Err(e) = result {
+            error!("[Handshake 🤝] failed ({:?})", e);
+            net_client.emit(NetEvent::HandshakeFailed(e));
+            net_client.close();
+        }
+    }
+}
+
+impl HandshakeResponder {
+    pub fn with_rng(rng: impl RngCore + Send + 'static) -> Self {
+        Self {
+            exchange: None,
+            rng: HandshakeRng::new(rng),
+        }
+    }
+
+    fn handle_challenge(
+        &mut self,
+        signature: Signature,
+        net_client: &mut NetClient,
+    ) -> Result<(), HandshakeError> {
+        let exchange = self
+            .exchange
+            .take()
+            .ok_or(HandshakeError::UnexpectedMessage)?;
+
+        <Responder as Challenge>::verify(&exchange, signature)
+            .map_err(|_| HandshakeError::ChallengeMismatch)?;
+
+        // update security with final blowfish instance
+        let key = <Responder as Challenge>::finalize(&exchange);
+        let blowfish = BlowfishCompat::new_from_slice(key.as_slice())
+            .expect("after exchange key should be valid");
+        net_client.security_mut().blowfish = Some(blowfish);
+
+        info!("[Handshake 🤝] completed with key exchange ✅!");
+        net_client.send(HandshakeMessage::Ack.into());
+        net_client.emit(NetEvent::HandshakeCompleted);
+
+        Ok(())
+    }
+
+    fn handle_setup(&mut self, setup: HandshakeSetup, net_client: &mut NetClient) {
+        let mut security_builder = SecurityBuilder::default();
+
+        info!("[Handshake 🤝] 🙋🏽‍♂️ Setting Up");
+
+        if let Some(key) = setup.blowfish {
+            security_builder = security_builder.blowfish(key);
+
+            info!("[Handshake 🤝] (in-)secure blowfish initialized");
+        }
+
+        if let Some(error_detection) = setup.error_detection {
+            security_builder = security_builder
+                .encoding_requirements((false, true))
+                .error_detection((error_detection.sequence, error_detection.checksum));
+
+            info!("[Handshake 🤝] error detection initialized");
+        }
+
+        net_client.set_security(security_builder.build());
+
+        if let Some(setup) = setup.exchange {
+            let exchange = Exchange::default()
+                .set_initial(setup.initial_key)
+                .set_generator(setup.generator)
+                .set_prime(setup.prime)
+                .set_private(self.rng.gen())
+                .remote(setup.public);
+
+            let signature = <Responder as Challenge>::create(&exchange);
+            let response = ExchangeResponse::new(exchange.get_local(), signature);
+            self.exchange = Some(exchange);
+
+            info!("[Handshake 🤝] responded to key exchange setup!");
+            net_client.send(HandshakeMessage::Response(response).into());
+        } else {
+            info!("[Handshake 🤝] completed without key exchange ✅!");
+            net_client.send(HandshakeMessage::Ack.into());
+            net_client.emit(NetEvent::HandshakeCompleted);
+        }
+    }
+}
@@ -0,0 +1,41 @@
+use rand::RngCore;
+
+/// The random source of a handshake, the thread RNG unless one was injected
+#[derive(Default)]
+pub(crate) struct HandshakeRng(Option<Box<dyn RngCore + Send>>);
+
+impl HandshakeRng {
+    pub(crate) fn new(rng: impl RngCore + Send + 'static) -> Self {
+        Self(Some(Box::new(rng)))
+    }
+}
+
+impl RngCore for HandshakeRng {
+    fn next_u32(&mut self) -> u32 {
+        match &mut self.0 {
+            Some(rng) => rng.next_u32(),
+            None => rand::thread_rng().next_u32(),
+        }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        match &mut self.0 {
+            Some(rng) => rng.next_u64(),
+            None => rand::thread_rng().next_u64(),
+        }
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        match &mut self.0 {
+            Some(rng) => rng.fill_bytes(dest),
+            None => rand::thread_rng().fill_bytes(dest),
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        match &mut self.0 {
+            Some(rng) => rng.try_fill_bytes(dest),
+            None => rand::thread_rng().try_fill_bytes(dest),
+        }
+    }
+}
@@ -0,0 +1,23 @@
+use crate::net::net_engine::HandshakeError;
+use crate::net::DisconnectReason;
+use crate::security::Desync;
+
+/// Something noteworthy that happened on a [NetClient](crate::net::NetClient)
+///
+/// Events are queued by the client and can be taken with
+/// [NetClient::take_event](crate::net::NetClient::take_event).
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum NetEvent {
+    /// An inbound message failed the error detection
+    Desync(Desync),
+
+    /// The NetEngine handshake completed and the final [Security](crate::security::Security) is
+    /// installed
+    HandshakeCompleted,
+
+    /// The NetEngine handshake failed
+    HandshakeFailed(HandshakeError),
+
+    /// The connection was closed and every message received before has been processed
+    Disconnected(DisconnectReason),
+}
@@ -0,0 +1,45 @@
+use crate::net::message::{Message, MAX_MESSAGE_SIZE};
+use std::fmt::{Display, Formatter};
+
+/// Why a [Message] could not be queued on a [NetConnection](crate::net::NetConnection)
+#[derive(Debug, PartialEq, Eq)]
+pub enum OutboundError {
+    /// The connection is closed
+    Closed(Message),
+
+    /// The outbound channel is full
+    Full(Message),
+
+    /// The message is larger than [MAX_MESSAGE_SIZE] on the wire. A
+    /// [NetClient](crate::net::NetClient) or [SilkroadCodec](crate::net::SilkroadCodec) splits
+    /// such messages into massive fragments.
+    Oversized(Message),
+}
+
+impl OutboundError {
+    /// Takes back the [Message] that was not queued
+    pub fn into_message(self) -> Message {
+        match self {
+            OutboundError::Closed(message)
+            | OutboundError::Full(message)
+            | OutboundError::Oversized(message) => message,
+        }
+    }
+}
+
+impl Display for OutboundError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OutboundError::Closed(_) => write!(f, "connection closed"),
+            OutboundError::Full(_) => write!(f, "outbound channel full"),
+            OutboundError::Oversized(m) => write!(
+                f,
+                "message of {} bytes exceeds {} bytes",
+                m.header().message_size(),
+                MAX_MESSAGE_SIZE
+            ),
+        }
+    }
+}
+
+impl std::error::Error for OutboundError {}
@@ -1,8 +0,0 @@
-
-pub use self::handshake_req::HandshakeReqProcessor;
-
-mod handshake_req;
-
-pub use self::ping::NetPing;
-pub use self::ping::ModuleIdentification;
-mod ping;
\ No newline at end of file
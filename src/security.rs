@@ -4,12 +4,23 @@ mod checksum;
 pub use self::sequencer::Sequencer;
 mod sequencer;
 
-pub use self::security::{Security, SecurityBuilder};
+pub use self::security::{
+    Desync, DesyncPolicy, InboundSecurity, OutboundSecurity, Security, SecurityBuilder,
+    SecurityError,
+};
 mod security;
 
+pub use self::security_state::SecurityState;
+mod security_state;
+
 pub mod blowfish_compat;
 
 pub use self::exchange::{
     Challenge, ChallengeMismatch, Exchange, Initiator, Key, NotSet, Responder, Set, Signature,
 };
 mod exchange;
+
+pub use self::exchange_parameters::{
+    find_generator, is_prime, random_prime, random_private, ExchangeParameters,
+};
+mod exchange_parameters;
@@ -26,6 +26,8 @@ extern crate bswap;
 pub(crate) const WORD: usize = 4;
 /// Size (length) of chunk/block
 pub const BLOCK_SIZE: usize = 8;
+/// Longest key blowfish accepts
+pub const MAX_KEY_SIZE: usize = 56;
 
 /**
 Takes data and reverses byte order inplace to fit
@@ -66,19 +68,36 @@ pub type Block = GenericArray<u8, U8>;
 #[derive(Clone, Copy)]
 pub struct BlowfishCompat {
     inner: Blowfish,
+    key: [u8; MAX_KEY_SIZE],
+    key_size: usize,
+}
+
+impl BlowfishCompat {
+    fn with_key(inner: Blowfish, key: &[u8]) -> Self {
+        let mut stored = [0; MAX_KEY_SIZE];
+        stored[..key.len()].copy_from_slice(key);
+        Self {
+            inner,
+            key: stored,
+            key_size: key.len(),
+        }
+    }
+
+    /// The key this instance was initialized with
+    pub fn key(&self) -> &[u8] {
+        &self.key[..self.key_size]
+    }
 }
 
 impl NewBlockCipher for BlowfishCompat {
     type KeySize = <Blowfish as NewBlockCipher>::KeySize;
 
     fn new(key: &GenericArray<u8, U56>) -> Self {
-        Self {
-            inner: <Blowfish as NewBlockCipher>::new(key),
-        }
+        Self::with_key(<Blowfish as NewBlockCipher>::new(key), key)
     }
 
     fn new_from_slice(key: &[u8]) -> Result<Self, InvalidLength> {
-        <Blowfish as NewBlockCipher>::new_from_slice(key).map(|bf| Self { inner: bf })
+        <Blowfish as NewBlockCipher>::new_from_slice(key).map(|bf| Self::with_key(bf, key))
     }
 }
 
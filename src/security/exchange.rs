@@ -126,9 +126,9 @@ pub trait Challenge {
 
     fn finalize(context: &Exchange<Set>) -> Key {
         let shared = context.shared();
-        let mut initial = context.initial.clone();
+        let mut initial = context.initial;
         transform_value(initial.as_mut(), shared, 3);
-        context.initial
+        initial
     }
 }
 
@@ -199,6 +199,7 @@ fn transform_value(value: &mut [u8], key: u32, key_byte: u8) {
 
 #[cfg(test)]
 mod tests {
+    use crate::security::exchange::transform_value;
     use crate::security::{Challenge, Exchange, Initiator, Key, NotSet, Responder};
 
     #[test]
@@ -232,4 +233,29 @@ mod tests {
         let initiator_challenge = <Initiator as Challenge>::create(&initiator);
         assert!(<Responder as Challenge>::verify(&responder, initiator_challenge).is_ok());
     }
+
+    #[test]
+    fn finalize_transforms_initial_key() {
+        let initial: Key = [0x32, 0xCE, 0xDD, 0x7C, 0xBC, 0xA8, 0x1F, 0x4A];
+        let initiator = Exchange::<NotSet>::default()
+            .set_initial(initial)
+            .set_generator(5)
+            .set_prime(0x7FFFFFFF)
+            .set_private(0x1234);
+        let responder = Exchange::<NotSet>::default()
+            .set_initial(initial)
+            .set_generator(5)
+            .set_prime(0x7FFFFFFF)
+            .set_private(0x4321)
+            .remote(initiator.get_local());
+        let initiator = initiator.remote(responder.get_local());
+
+        let mut expected = initial;
+        transform_value(expected.as_mut(), initiator.shared(), 3);
+
+        let final_key = <Initiator as Challenge>::finalize(&initiator);
+        assert_eq!(final_key, expected);
+        assert_ne!(final_key, initial);
+        assert_eq!(final_key, <Responder as Challenge>::finalize(&responder));
+    }
 }
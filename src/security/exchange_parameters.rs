@@ -0,0 +1,244 @@
+use crate::security::{Exchange, NotSet};
+use rand::{Rng, RngCore};
+
+/// Bases that make Miller-Rabin deterministic for every `u32`
+const WITNESSES: [u64; 3] = [2, 7, 61];
+
+/// Group parameters and a private exponent for an [Exchange]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct ExchangeParameters {
+    pub generator: u32,
+    pub prime: u32,
+    pub private: u32,
+}
+
+impl ExchangeParameters {
+    /// Picks a random 31-bit prime, a generator of its multiplicative group and a private
+    /// exponent in `2..prime - 1`.
+    pub fn random() -> Self {
+        Self::generate(&mut rand::thread_rng())
+    }
+
+    /// Like [ExchangeParameters::random], drawing from `rng`.
+    pub fn generate<R: RngCore + ?Sized>(rng: &mut R) -> Self {
+        let prime = random_prime(rng);
+        Self {
+            generator: find_generator(prime, rng),
+            prime,
+            private: random_private(prime, rng),
+        }
+    }
+}
+
+impl Exchange<NotSet> {
+    /// An exchange with a random initial key and [ExchangeParameters::random].
+    pub fn random() -> Self {
+        Self::generate(&mut rand::thread_rng())
+    }
+
+    /// Like [Exchange::random], drawing from `rng`.
+    pub fn generate<R: RngCore + ?Sized>(rng: &mut R) -> Self {
+        Exchange::default()
+            .set_initial(rng.gen())
+            .set_parameters(ExchangeParameters::generate(rng))
+    }
+}
+
+impl<TRemote> Exchange<TRemote> {
+    pub fn set_parameters(self, parameters: ExchangeParameters) -> Self {
+        self.set_generator(parameters.generator)
+            .set_prime(parameters.prime)
+            .set_private(parameters.private)
+    }
+}
+
+/// A random prime with exactly 31 bits, so products modulo it fit in an `i64`.
+pub fn random_prime<R: RngCore + ?Sized>(rng: &mut R) -> u32 {
+    loop {
+        let candidate = (rng.gen::<u32>() & 0x7FFFFFFF) | 0x40000001;
+        if is_prime(candidate) {
+            return candidate;
+        }
+    }
+}
+
+/// A random generator of the multiplicative group modulo `prime`
+///
+/// `g` generates the group if `g^((prime - 1) / q) != 1` for every prime factor `q` of
+/// `prime - 1`.
+pub fn find_generator<R: RngCore + ?Sized>(prime: u32, rng: &mut R) -> u32 {
+    assert!(
+        prime > 3 && is_prime(prime),
+        "{} is not a prime above 3",
+        prime
+    );
+
+    let order = prime as u64 - 1;
+    let factors = prime_factors(order);
+    loop {
+        let candidate = rng.gen_range(2..prime - 1) as u64;
+        if factors
+            .iter()
+            .all(|factor| pow_mod(candidate, order / factor, prime as u64) != 1)
+        {
+            return candidate as u32;
+        }
+    }
+}
+
+/// A random private exponent in `2..prime - 1`
+pub fn random_private<R: RngCore + ?Sized>(prime: u32, rng: &mut R) -> u32 {
+    assert!(
+        prime > 3,
+        "{} is too small to pick a private exponent",
+        prime
+    );
+    rng.gen_range(2..prime - 1)
+}
+
+/// Deterministic Miller-Rabin test
+pub fn is_prime(n: u32) -> bool {
+    let n = n as u64;
+    if n < 2 {
+        return false;
+    }
+    for witness in WITNESSES {
+        if n == witness {
+            return true;
+        }
+        if n.is_multiple_of(witness) {
+            return false;
+        }
+    }
+
+    let s = (n - 1).trailing_zeros();
+    let d = (n - 1) >> s;
+
+    'witness: for witness in WITNESSES {
+        let mut x = pow_mod(witness, d, n);
+        if x == 1 || x == n - 1 {
+            continue;
+        }
+        for _ in 1..s {
+            x = x * x % n;
+            if x == n - 1 {
+                continue 'witness;
+            }
+        }
+        return false;
+    }
+    true
+}
+
+/// The distinct prime factors of `n`, by trial division
+fn prime_factors(mut n: u64) -> Vec<u64> {
+    let mut factors = Vec::new();
+    let mut factor = 2;
+    while factor * factor <= n {
+        if n.is_multiple_of(factor) {
+            factors.push(factor);
+            while n.is_multiple_of(factor) {
+                n /= factor;
+            }
+        }
+        factor += if factor == 2 { 1 } else { 2 };
+    }
+    if n > 1 {
+        factors.push(n);
+    }
+    factors
+}
+
+/// `base^exponent mod modulus` for moduli below `2^32`
+fn pow_mod(mut base: u64, mut exponent: u64, modulus: u64) -> u64 {
+    let mut result = 1;
+    base %= modulus;
+    while exponent != 0 {
+        if exponent & 1 == 1 {
+            result = result * base % modulus;
+        }
+        exponent >>= 1;
+        base = base * base % modulus;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::security::exchange_parameters::{
+        find_generator, is_prime, prime_factors, random_prime,
+    };
+    use crate::security::{Exchange, ExchangeParameters};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn is_prime_matches_trial_division() {
+        for n in 0..10_000u32 {
+            let expected = n >= 2 && (2..n).take_while(|d| d * d <= n).all(|d| n % d != 0);
+            assert_eq!(is_prime(n), expected, "{}", n);
+        }
+    }
+
+    #[test]
+    fn is_prime_large() {
+        assert!(is_prime(2_147_483_647));
+        assert!(is_prime(4_294_967_291));
+        // strong pseudoprimes to several small bases
+        assert!(!is_prime(3_215_031_751));
+        assert!(!is_prime(25_326_001));
+    }
+
+    #[test]
+    fn random_prime_has_31_bits() {
+        for _ in 0..16 {
+            let prime = random_prime(&mut rand::thread_rng());
+            assert!(is_prime(prime));
+            assert_eq!(prime >> 30, 1);
+        }
+    }
+
+    #[test]
+    fn generator_generates_group() {
+        // 2 and 4 have order 3 modulo 7, only 3 and 5 generate the group
+        assert_eq!(prime_factors(6), vec![2, 3]);
+        for _ in 0..16 {
+            assert!([3, 5].contains(&find_generator(7, &mut rand::thread_rng())));
+        }
+
+        let prime = 23;
+        let generator = find_generator(prime, &mut rand::thread_rng());
+        let mut seen = vec![false; prime as usize];
+        let mut value = 1u32;
+        for _ in 0..prime - 1 {
+            value = value * generator % prime;
+            seen[value as usize] = true;
+        }
+        assert_eq!(
+            seen.iter().filter(|seen| **seen).count(),
+            prime as usize - 1
+        );
+    }
+
+    #[test]
+    fn parameters() {
+        let parameters = ExchangeParameters::random();
+        assert!(is_prime(parameters.prime));
+        assert!((2..parameters.prime - 1).contains(&parameters.generator));
+        assert!((2..parameters.prime - 1).contains(&parameters.private));
+    }
+
+    #[test]
+    fn seeded_generation_is_reproducible() {
+        let parameters = ExchangeParameters::generate(&mut StdRng::seed_from_u64(7));
+        assert_eq!(
+            parameters,
+            ExchangeParameters::generate(&mut StdRng::seed_from_u64(7))
+        );
+
+        let exchange = Exchange::generate(&mut StdRng::seed_from_u64(7));
+        let again = Exchange::generate(&mut StdRng::seed_from_u64(7));
+        assert_eq!(exchange.get_initial(), again.get_initial());
+        assert_eq!(exchange.get_local(), again.get_local());
+    }
+}
@@ -1,11 +1,14 @@
-use crate::net::message::Message;
+use crate::net::message::{Header, Message, HEADER_SIZE};
+use crate::security::blowfish_compat::cipher::errors::InvalidLength;
 use crate::security::blowfish_compat::{
-    Block, BlockDecrypt, BlockEncrypt, BlowfishCompat, NewBlockCipher,
+    BlockDecrypt, BlockEncrypt, BlowfishCompat, NewBlockCipher, BLOCK_SIZE,
 };
-use crate::security::{Checksum, Key, Sequencer};
+use crate::security::{Checksum, Key, SecurityState, Sequencer};
 use bytes::{Buf, BufMut, Bytes, BytesMut};
-use log::{error, warn};
+use log::warn;
+use rand::{Rng, RngCore};
 
+#[derive(Clone, Copy)]
 pub struct EncodingRequirements {
     outbound: bool,
     inbound: bool,
@@ -17,9 +20,12 @@ impl EncodingRequirements {
     }
 }
 
+/// The error detection state, each direction advances its own [Sequencer]
+#[derive(Clone)]
 struct Encoder {
     requirements: EncodingRequirements,
-    sequencer: Sequencer,
+    outbound_sequencer: Sequencer,
+    inbound_sequencer: Sequencer,
     checksum: Checksum,
 }
 
@@ -27,7 +33,8 @@ impl Encoder {
     fn new(inbound: bool, outbound: bool, sequencer_seed: u32, checksum_seed: u32) -> Self {
         Self {
             requirements: EncodingRequirements::new(inbound, outbound),
-            sequencer: Sequencer::new(sequencer_seed),
+            outbound_sequencer: Sequencer::new(sequencer_seed),
+            inbound_sequencer: Sequencer::new(sequencer_seed),
             checksum: Checksum::new(checksum_seed),
         }
     }
@@ -37,7 +44,8 @@ impl Default for Encoder {
     fn default() -> Self {
         Self {
             checksum: Checksum::default(),
-            sequencer: Sequencer::default(),
+            outbound_sequencer: Sequencer::default(),
+            inbound_sequencer: Sequencer::default(),
             requirements: EncodingRequirements::new(false, false),
         }
     }
@@ -58,55 +66,278 @@ impl Default for Security {
 }
 
 impl Security {
-    pub fn encode(&mut self, mut message: Message) -> Message {
-        if !self.encoder.requirements.outbound {
-            return message;
+    /// Prepares an outbound [Message] for the wire.
+    ///
+    /// If error detection is enabled for outbound messages, the sequence and checksum bytes are
+    /// stamped into the header. If the header is flagged as encrypted, everything after the size
+    /// field is padded to whole blocks and encrypted with blowfish afterwards. The checksum is
+    /// always computed over the plain message (including the encryption flag), just like the
+    /// game client does.
+    pub fn encode(&mut self, message: Message) -> Message {
+        encode(&self.blowfish, &mut self.encoder, message)
+    }
+
+    /// Encrypts `data` in place, block by block.
+    ///
+    /// The length of `data` has to be a multiple of [BLOCK_SIZE].
+    pub fn encrypt(&self, data: &mut [u8]) {
+        encrypt(&self.blowfish, data)
+    }
+
+    /// Decrypts an inbound [Message] if its header is flagged as encrypted.
+    ///
+    /// All blocks behind the size field are decrypted and the padding is trimmed back to the size
+    /// declared in the header. The encryption flag is kept, so the message is encrypted again
+    /// when it gets forwarded.
+    pub fn decrypt(&self, message: Message) -> Result<Message, SecurityError> {
+        decrypt(&self.blowfish, message)
+    }
+
+    /// Verifies the sequence and checksum bytes of a decrypted inbound [Message].
+    ///
+    /// Does nothing unless error detection is enabled for inbound messages. The sequencer always
+    /// advances, so a single bad message does not desynchronize every following one.
+    pub fn verify(&mut self, message: &Message) -> Result<(), Desync> {
+        verify(&mut self.encoder, message)
+    }
+
+    /// Splits into an [InboundSecurity] and an [OutboundSecurity], so reading and writing can
+    /// happen in separate tasks.
+    ///
+    /// Both halves get the blowfish key and their own copy of the error detection state.
+    pub fn split(self) -> (InboundSecurity, OutboundSecurity) {
+        let inbound = InboundSecurity {
+            blowfish: self.blowfish,
+            encoder: self.encoder.clone(),
+        };
+        let outbound = OutboundSecurity {
+            blowfish: self.blowfish,
+            encoder: self.encoder,
+        };
+
+        (inbound, outbound)
+    }
+
+    /// Takes a snapshot of the blowfish key and the error detection state.
+    pub fn export(&self) -> SecurityState {
+        SecurityState {
+            blowfish_key: self.blowfish.map(|blowfish| blowfish.key().to_vec()),
+            encoding_requirements: (
+                self.encoder.requirements.inbound,
+                self.encoder.requirements.outbound,
+            ),
+            outbound_sequencer: self.encoder.outbound_sequencer.bytes(),
+            inbound_sequencer: self.encoder.inbound_sequencer.bytes(),
+            checksum_seed: self.encoder.checksum.seed(),
         }
+    }
+
+    /// Restores a [Security] from a snapshot taken with [Security::export].
+    ///
+    /// Fails if the snapshot holds a key blowfish does not accept.
+    pub fn import(state: SecurityState) -> Result<Self, InvalidLength> {
+        let blowfish = state
+            .blowfish_key
+            .map(|key| BlowfishCompat::new_from_slice(&key))
+            .transpose()?;
+        let (inbound, outbound) = state.encoding_requirements;
+
+        Ok(Self {
+            blowfish,
+            encoder: Encoder {
+                requirements: EncodingRequirements::new(inbound, outbound),
+                outbound_sequencer: Sequencer::from_bytes(state.outbound_sequencer),
+                inbound_sequencer: Sequencer::from_bytes(state.inbound_sequencer),
+                checksum: Checksum::new(state.checksum_seed.into()),
+            },
+        })
+    }
+}
+
+/// The half of a [Security] that decrypts and verifies inbound messages
+pub struct InboundSecurity {
+    blowfish: Option<BlowfishCompat>,
+    encoder: Encoder,
+}
+
+impl InboundSecurity {
+    /// See [Security::decrypt]
+    pub fn decrypt(&self, message: Message) -> Result<Message, SecurityError> {
+        decrypt(&self.blowfish, message)
+    }
+
+    /// See [Security::verify]
+    pub fn verify(&mut self, message: &Message) -> Result<(), Desync> {
+        verify(&mut self.encoder, message)
+    }
+}
 
-        let encoder = &mut self.encoder;
-        message.header_mut().sequence = encoder.sequencer.next();
+/// The half of a [Security] that encodes outbound messages
+pub struct OutboundSecurity {
+    blowfish: Option<BlowfishCompat>,
+    encoder: Encoder,
+}
+
+impl OutboundSecurity {
+    /// See [Security::encode]
+    pub fn encode(&mut self, message: Message) -> Message {
+        encode(&self.blowfish, &mut self.encoder, message)
+    }
+
+    /// See [Security::encrypt]
+    pub fn encrypt(&self, data: &mut [u8]) {
+        encrypt(&self.blowfish, data)
+    }
+}
+
+fn encode(
+    blowfish: &Option<BlowfishCompat>,
+    encoder: &mut Encoder,
+    mut message: Message,
+) -> Message {
+    if message.header().is_encrypted() && blowfish.is_none() {
+        warn!("encode called for an encrypted message with uninitialized blowfish!");
+        message.header_mut().set_encrypted(false);
+    }
+
+    if encoder.requirements.outbound {
+        message.header_mut().sequence = encoder.outbound_sequencer.next();
         message.header_mut().checksum = 0;
 
-        let bytes: Bytes = message.into();
+        let bytes: Bytes = message.clone().into();
         let checksum = encoder.checksum.compute(bytes.as_ref(), bytes.len());
+        message.header_mut().checksum = checksum;
+    }
 
-        let mut message = Message::from(bytes);
+    if message.header().is_encrypted() {
+        let mut bytes: Bytes = message.into();
+        let size = bytes.get_u16_le();
 
-        message.header_mut().checksum = checksum;
+        let mut remaining = bytes.to_vec();
+        remaining.resize(remaining.len().next_multiple_of(BLOCK_SIZE), 0);
+        encrypt(blowfish, remaining.as_mut_slice());
 
-        message
+        message = reassemble(size, &remaining);
     }
 
-    pub fn encrypt(&self, data: &mut [u8]) {
-        if let Some(blowfish) = self.blowfish {
-            blowfish.encrypt_block(Block::from_mut_slice(data));
-        } else {
-            warn!("encrypt called with uninitialized blowfish!");
+    message
+}
+
+fn encrypt(blowfish: &Option<BlowfishCompat>, data: &mut [u8]) {
+    debug_assert!(data.len().is_multiple_of(BLOCK_SIZE));
+
+    if let Some(blowfish) = blowfish {
+        for block in data.chunks_exact_mut(BLOCK_SIZE) {
+            blowfish.encrypt_block(block.into());
         }
+    } else {
+        warn!("encrypt called with uninitialized blowfish!");
     }
+}
 
-    pub fn decrypt(&self, message: Message) -> Message {
-        if message.is_encrypted() {
-            if let Some(blowfish) = self.blowfish {
-                let mut bytes: Bytes = message.into();
-                let size = bytes.get_u16_le();
-                let mut remaining = bytes.to_vec();
-                let data = remaining.as_mut_slice();
-                blowfish.decrypt_block(Block::from_mut_slice(data));
+fn decrypt(blowfish: &Option<BlowfishCompat>, message: Message) -> Result<Message, SecurityError> {
+    if !message.is_encrypted() {
+        return Ok(message);
+    }
 
-                let mut mem = BytesMut::new();
-                mem.put_u16_le(size);
-                mem.put_slice(data);
+    let blowfish = blowfish.ok_or(SecurityError::BlowfishMissing)?;
+    let declared = message.header().data_size() as usize + HEADER_SIZE - 2;
 
-                mem.freeze().into()
-            } else {
-                error!("received encrypted message, but blowfish is not setup!");
-                panic!();
-            }
-        } else {
-            message
-        }
+    let mut bytes: Bytes = message.into();
+    let size = bytes.get_u16_le();
+    let mut remaining = bytes.to_vec();
+
+    if !remaining.len().is_multiple_of(BLOCK_SIZE) {
+        return Err(SecurityError::InvalidBlockLength(remaining.len()));
+    }
+
+    if remaining.len() < declared {
+        return Err(SecurityError::Truncated {
+            declared,
+            actual: remaining.len(),
+        });
     }
+
+    for block in remaining.chunks_exact_mut(BLOCK_SIZE) {
+        blowfish.decrypt_block(block.into());
+    }
+    remaining.truncate(declared);
+
+    Ok(reassemble(size, &remaining))
+}
+
+fn verify(encoder: &mut Encoder, message: &Message) -> Result<(), Desync> {
+    if !encoder.requirements.inbound {
+        return Ok(());
+    }
+
+    let expected = encoder.inbound_sequencer.next();
+    let actual = message.header().sequence;
+    if expected != actual {
+        return Err(Desync::Sequence { expected, actual });
+    }
+
+    let mut message = message.clone();
+    let actual = message.header().checksum;
+    message.header_mut().checksum = 0;
+
+    let bytes: Bytes = message.into();
+    let expected = encoder.checksum.compute(bytes.as_ref(), bytes.len());
+    if expected != actual {
+        return Err(Desync::Checksum { expected, actual });
+    }
+
+    Ok(())
+}
+
+/// Puts a [Message] back together from its size field and everything behind it
+fn reassemble(size: u16, remaining: &[u8]) -> Message {
+    let mut mem = BytesMut::new();
+    mem.put_u16_le(size);
+    mem.put_slice(remaining);
+
+    let mut data = mem.freeze();
+    let header = Header::try_from(data.split_to(HEADER_SIZE))
+        .expect("id and security bytes are always part of the remaining bytes");
+
+    Message::from((header, data))
+}
+
+/// An inbound [Message] failed the error detection
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Desync {
+    /// The sequence byte does not match the local [Sequencer](crate::security::Sequencer)
+    Sequence { expected: u8, actual: u8 },
+
+    /// The checksum byte does not match the one computed by the local
+    /// [Checksum](crate::security::Checksum)
+    Checksum { expected: u8, actual: u8 },
+}
+
+/// How a [NetClient](crate::net::NetClient) reacts to a [Desync]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum DesyncPolicy {
+    /// Discard the message
+    #[default]
+    Drop,
+
+    /// Log the mismatch and process the message anyway
+    Log,
+
+    /// Close the connection
+    Disconnect,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SecurityError {
+    /// Received an encrypted [Message], but blowfish has not been set up
+    BlowfishMissing,
+
+    /// The encrypted part of a [Message] is not a multiple of [BLOCK_SIZE]
+    InvalidBlockLength(usize),
+
+    /// The encrypted part of a [Message] is shorter than the size declared in its header
+    Truncated { declared: usize, actual: usize },
 }
 
 pub struct SecurityBuilder {
@@ -156,6 +387,16 @@ impl SecurityBuilder {
         }
     }
 
+    /// Uses a blowfish key drawn from `rng`, e.g. `&mut rand::thread_rng()`.
+    pub fn random_blowfish<R: RngCore + ?Sized>(self, rng: &mut R) -> Self {
+        self.blowfish(rng.gen())
+    }
+
+    /// Uses sequencer and checksum seeds drawn from `rng`, e.g. `&mut rand::thread_rng()`.
+    pub fn random_error_detection<R: RngCore + ?Sized>(self, rng: &mut R) -> Self {
+        self.error_detection((rng.gen(), rng.gen()))
+    }
+
     pub fn build(self) -> Security {
         Security {
             blowfish: self.key.map(|k| {
@@ -171,3 +412,234 @@ impl SecurityBuilder {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::net::io::{from_bytes, to_bytes};
+    use crate::net::message::MessageDirection::Req;
+    use crate::net::message::MessageKind::Game;
+    use crate::net::message::{Message, HEADER_SIZE};
+    use crate::security::blowfish_compat::{BlockDecrypt, BlowfishCompat, NewBlockCipher};
+    use crate::security::{Checksum, Desync, Security, SecurityBuilder, SecurityError, Sequencer};
+    use crate::test_support::KEY;
+    use bytes::Bytes;
+
+    #[test]
+    fn encode_encrypts_and_pads_to_blocks() {
+        let mut security = SecurityBuilder::default()
+            .blowfish(KEY)
+            .encoding_requirements((false, true))
+            .error_detection((0x1234, 0x5678))
+            .build();
+
+        let mut message = Message::new(Req, Game, 1, Bytes::from_static(&[1, 2, 3]));
+        message.header_mut().set_encrypted(true);
+
+        let bytes: Bytes = security.encode(message).into();
+
+        // 2 bytes size + (2 bytes id + 2 bytes security + 3 bytes data) padded to 8
+        assert_eq!(bytes.len(), 2 + 8);
+        assert_eq!(u16::from_le_bytes([bytes[0], bytes[1]]), 0x8003);
+
+        let blowfish = BlowfishCompat::new_from_slice(&KEY).unwrap();
+        let mut plain = bytes.to_vec();
+        blowfish.decrypt_block((&mut plain[2..]).into());
+
+        let expected_sequence = Sequencer::new(0x1234).next();
+        assert_eq!(&plain[2..4], &[0x01, 0x70]);
+        assert_eq!(plain[4], expected_sequence);
+        assert_eq!(&plain[HEADER_SIZE..HEADER_SIZE + 3], &[1, 2, 3]);
+
+        // checksum covers the plain message with the encryption flag set
+        let mut check = plain[..HEADER_SIZE + 3].to_vec();
+        check[5] = 0;
+        let checksum = Checksum::new(0x5678).compute(&check, check.len());
+        assert_eq!(plain[5], checksum);
+    }
+
+    #[test]
+    fn encode_without_encryption_keeps_payload() {
+        let mut security = SecurityBuilder::default().blowfish(KEY).build();
+        let message = Message::new(Req, Game, 1, Bytes::from_static(&[1, 2, 3]));
+
+        assert_eq!(security.encode(message.clone()), message);
+    }
+
+    #[test]
+    fn decrypt_restores_encoded_message() {
+        let mut security = SecurityBuilder::default().blowfish(KEY).build();
+        let data = Bytes::from_static(&[0xAA; 21]);
+
+        let mut message = Message::new(Req, Game, 1, data.clone());
+        message.header_mut().set_encrypted(true);
+
+        let encoded = security.encode(message.clone());
+        assert_eq!(encoded.header().message_size(), 2 + 32);
+
+        let decrypted = security.decrypt(encoded).unwrap();
+        assert!(decrypted.is_encrypted());
+        assert_eq!(decrypted, message);
+    }
+
+    #[test]
+    fn decrypt_fails_without_blowfish() {
+        let mut message = Message::new(Req, Game, 1, Bytes::from_static(&[1, 2, 3]));
+        message.header_mut().set_encrypted(true);
+
+        let security = SecurityBuilder::default().build();
+        assert_eq!(
+            security.decrypt(message),
+            Err(SecurityError::BlowfishMissing)
+        );
+    }
+
+    #[test]
+    fn decrypt_rejects_partial_blocks() {
+        let mut message = Message::new(Req, Game, 1, Bytes::from_static(&[1, 2, 3]));
+        message.header_mut().set_encrypted(true);
+
+        let security = SecurityBuilder::default().blowfish(KEY).build();
+        assert_eq!(
+            security.decrypt(message),
+            Err(SecurityError::InvalidBlockLength(7))
+        );
+    }
+
+    #[test]
+    fn verify_accepts_encoded_messages() {
+        let mut local = SecurityBuilder::default()
+            .encoding_requirements((false, true))
+            .error_detection((0x1234, 0x5678))
+            .build();
+        let mut remote = SecurityBuilder::default()
+            .encoding_requirements((true, false))
+            .error_detection((0x1234, 0x5678))
+            .build();
+
+        for op in 0..3 {
+            let message = local.encode(Message::new(Req, Game, op, Bytes::from(vec![op as u8])));
+            assert_eq!(remote.verify(&message), Ok(()));
+        }
+    }
+
+    #[test]
+    fn verify_detects_tampered_and_replayed_messages() {
+        let mut local = SecurityBuilder::default()
+            .encoding_requirements((false, true))
+            .error_detection((0x1234, 0x5678))
+            .build();
+        let mut remote = SecurityBuilder::default()
+            .encoding_requirements((true, false))
+            .error_detection((0x1234, 0x5678))
+            .build();
+
+        let message = local.encode(Message::new(Req, Game, 1, Bytes::from_static(&[1, 2, 3])));
+        let bytes: Bytes = message.clone().into();
+        let mut tampered = bytes.to_vec();
+        tampered[HEADER_SIZE] ^= 0xFF;
+        assert!(matches!(
+            remote.verify(&Message::try_from(tampered.as_slice()).unwrap()),
+            Err(Desync::Checksum { .. })
+        ));
+
+        assert!(matches!(
+            remote.verify(&message),
+            Err(Desync::Sequence { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn split_halves_run_in_separate_tasks() {
+        let (_, mut outbound) = SecurityBuilder::default()
+            .blowfish(KEY)
+            .encoding_requirements((false, true))
+            .error_detection((0x1234, 0x5678))
+            .build()
+            .split();
+        let (mut inbound, _) = SecurityBuilder::default()
+            .blowfish(KEY)
+            .encoding_requirements((true, false))
+            .error_detection((0x1234, 0x5678))
+            .build()
+            .split();
+
+        let mut message = Message::new(Req, Game, 1, Bytes::from_static(&[1, 2, 3]));
+        message.header_mut().set_encrypted(true);
+
+        let writer = tokio::spawn(async move {
+            (0..3)
+                .map(|_| outbound.encode(message.clone()))
+                .collect::<Vec<_>>()
+        });
+        let encoded = writer.await.unwrap();
+
+        let reader = tokio::spawn(async move {
+            for message in encoded {
+                let message = inbound.decrypt(message).unwrap();
+                inbound.verify(&message).unwrap();
+                assert_eq!(message.reader(), Bytes::from_static(&[1, 2, 3]));
+            }
+        });
+        reader.await.unwrap();
+    }
+
+    #[test]
+    fn verify_and_encode_keep_separate_sequences() {
+        let mut local = SecurityBuilder::default()
+            .encoding_requirements((true, true))
+            .error_detection((0x1234, 0x5678))
+            .build();
+        let mut remote = SecurityBuilder::default()
+            .encoding_requirements((true, true))
+            .error_detection((0x1234, 0x5678))
+            .build();
+
+        for op in 0..3 {
+            let request = local.encode(Message::new(Req, Game, op, Bytes::from(vec![op as u8])));
+            assert_eq!(remote.verify(&request), Ok(()));
+
+            let response = remote.encode(Message::new(Req, Game, op, Bytes::from(vec![op as u8])));
+            assert_eq!(local.verify(&response), Ok(()));
+        }
+    }
+
+    #[test]
+    fn imported_state_continues_session() {
+        let mut local = SecurityBuilder::default()
+            .blowfish(KEY)
+            .encoding_requirements((false, true))
+            .error_detection((0x1234, 0x5678))
+            .build();
+        let mut remote = SecurityBuilder::default()
+            .blowfish(KEY)
+            .encoding_requirements((true, false))
+            .error_detection((0x1234, 0x5678))
+            .build();
+
+        let mut message = Message::new(Req, Game, 1, Bytes::from_static(&[1, 2, 3]));
+        message.header_mut().set_encrypted(true);
+        for _ in 0..3 {
+            let encoded = local.encode(message.clone());
+            remote.verify(&remote.decrypt(encoded).unwrap()).unwrap();
+        }
+
+        let state = local.export();
+        assert_eq!(state.blowfish_key.as_deref(), Some(KEY.as_slice()));
+        let bytes = to_bytes(&state).unwrap();
+        let mut restored = Security::import(from_bytes(bytes).unwrap()).unwrap();
+        assert_eq!(restored.export(), state);
+
+        let encoded = restored.encode(message.clone());
+        assert_eq!(encoded, local.encode(message));
+        assert_eq!(remote.verify(&remote.decrypt(encoded).unwrap()), Ok(()));
+    }
+
+    #[test]
+    fn import_rejects_invalid_key() {
+        let mut state = Security::default().export();
+        assert_eq!(state.blowfish_key, None);
+
+        state.blowfish_key = Some(vec![1, 2]);
+        assert!(Security::import(state).is_err());
+    }
+}
@@ -0,0 +1,25 @@
+use serde::{Deserialize, Serialize};
+
+/// The complete state of a [Security](crate::security::Security), taken with
+/// [Security::export](crate::security::Security::export)
+///
+/// Restoring it with [Security::import](crate::security::Security::import) continues the session
+/// where the snapshot was taken, so it has to be taken between two messages. It can be written
+/// with any serde format, e.g. [to_bytes](crate::net::io::to_bytes).
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct SecurityState {
+    /// `None` until a key is known
+    pub blowfish_key: Option<Vec<u8>>,
+
+    pub encoding_requirements: (/* inbound */ bool, /* outbound */ bool),
+
+    /// The [Sequencer](crate::security::Sequencer) stamping outbound messages, see
+    /// [Sequencer::bytes](crate::security::Sequencer::bytes)
+    pub outbound_sequencer: [u8; 3],
+
+    /// The [Sequencer](crate::security::Sequencer) verifying inbound messages
+    pub inbound_sequencer: [u8; 3],
+
+    /// See [Checksum::seed](crate::security::Checksum::seed)
+    pub checksum_seed: u8,
+}
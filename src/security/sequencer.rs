@@ -1,5 +1,6 @@
 const DEFAULT_SEED: u32 = 0x9ABFB3B6;
 
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Sequencer {
     byte0: u8,
     byte1: u8,
@@ -33,6 +34,20 @@ impl Sequencer {
         }
     }
 
+    /// The current state as `[byte0, byte1, byte2]`, see [Sequencer::from_bytes]
+    pub fn bytes(&self) -> [u8; 3] {
+        [self.byte0, self.byte1, self.byte2]
+    }
+
+    /// Continues the sequence from a state taken with [Sequencer::bytes].
+    pub fn from_bytes([byte0, byte1, byte2]: [u8; 3]) -> Self {
+        Self {
+            byte0,
+            byte1,
+            byte2,
+        }
+    }
+
     pub fn next(&mut self) -> u8 {
         let value = (self.byte2 as u32 * (!self.byte0 as u32 + self.byte1 as u32)) as u8;
         self.byte0 = (value ^ value >> 4) as u8;
@@ -87,4 +102,14 @@ mod tests {
         assert_eq!(m0, 1706579037);
         assert_eq!(m1, 1019020591);
     }
+
+    #[test]
+    fn from_bytes_continues_sequence() {
+        let mut sequencer = Sequencer::new(0x1234);
+        sequencer.next();
+
+        let mut restored = Sequencer::from_bytes(sequencer.bytes());
+        assert_eq!(restored, sequencer);
+        assert_eq!(restored.next(), 222);
+    }
 }
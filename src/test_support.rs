@@ -0,0 +1,19 @@
+//! Fixtures shared by the unit tests
+
+use crate::security::Key;
+use tokio::net::{TcpListener, TcpStream};
+
+/// Blowfish key the tests encrypt with
+pub(crate) const KEY: Key = [0x32, 0xCE, 0xDD, 0x7C, 0xBC, 0xA8, 0x1F, 0x4A];
+
+/// Connects two streams through a listener on a free local port.
+///
+/// Returns the connecting side first and the accepted side second.
+pub(crate) async fn tcp_pair() -> (TcpStream, TcpStream) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let connecting = TcpStream::connect(addr).await.unwrap();
+    let (accepted, _) = listener.accept().await.unwrap();
+    (connecting, accepted)
+}